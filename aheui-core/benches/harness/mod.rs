@@ -0,0 +1,125 @@
+//! A small benchmark harness in the manner of criterion, for the benches that run whole
+//! programs. Each benchmark is warmed up, then timed in a number of samples, and its median
+//! is compared with the one of the previous run, which is kept under `target/aheui-bench`.
+//! The bench fails if any of them got slower beyond the noise, so that running it before and
+//! after a change guards against regressions.
+//!
+//! Arguments that don't start with `--` filter the benchmarks by their names.
+
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+const WARM_UP: Duration = Duration::from_millis(500);
+const MEASUREMENT: Duration = Duration::from_secs(3);
+const SAMPLES: u32 = 20;
+/// Changes of the median smaller than this are taken as noise.
+const THRESHOLD: f64 = 0.05;
+
+pub struct Harness {
+    filters: Vec<String>,
+    baselines: Option<PathBuf>,
+    regressed: Vec<String>,
+}
+
+impl Harness {
+    pub fn from_args() -> Harness {
+        let filters = std::env::args()
+            .skip(1)
+            .filter(|arg| !arg.starts_with("--"))
+            .collect();
+        // The bench executable is in `target/<profile>/deps`.
+        let baselines = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.parent()?.parent()?.parent()?.join("aheui-bench")));
+        Harness {
+            filters,
+            baselines,
+            regressed: Vec::new(),
+        }
+    }
+
+    pub fn bench(&mut self, name: &str, mut f: impl FnMut()) {
+        if !self.filters.is_empty() && !self.filters.iter().any(|filter| name.contains(filter)) {
+            return;
+        }
+
+        let start = Instant::now();
+        let mut iterations = 0u32;
+        while iterations == 0 || start.elapsed() < WARM_UP {
+            f();
+            iterations += 1;
+        }
+        let estimate = start.elapsed() / iterations;
+        let per_sample = (MEASUREMENT / SAMPLES).as_nanos() / estimate.as_nanos().max(1);
+        let per_sample = per_sample.clamp(1, u32::MAX as u128) as u32;
+
+        let mut samples: Vec<Duration> = (0..SAMPLES)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..per_sample {
+                    f();
+                }
+                start.elapsed() / per_sample
+            })
+            .collect();
+        samples.sort();
+        let median = samples[samples.len() / 2];
+        println!(
+            "{:<32} time: [{} {} {}]",
+            name,
+            format_duration(samples[0]),
+            format_duration(median),
+            format_duration(samples[samples.len() - 1]),
+        );
+        if let Some(previous) = self.swap_baseline(name, median) {
+            let change = (median.as_secs_f64() / previous.as_secs_f64() - 1.0) * 100.0;
+            println!(
+                "{:<32} change: {:+.2}% (previously {})",
+                "",
+                change,
+                format_duration(previous)
+            );
+            // The fastest sample is slower than the previous median.
+            if change > THRESHOLD * 100.0 && samples[0] > previous {
+                println!("{:<32} regressed", "");
+                self.regressed.push(name.to_string());
+            }
+        }
+    }
+
+    /// Exits with an error if any benchmark regressed.
+    pub fn finish(self) {
+        if !self.regressed.is_empty() {
+            eprintln!("regressed: {}", self.regressed.join(", "));
+            std::process::exit(1);
+        }
+    }
+
+    /// Saves `median` as the baseline of `name`, and returns the previous one.
+    fn swap_baseline(&self, name: &str, median: Duration) -> Option<Duration> {
+        let dir = self.baselines.as_ref()?;
+        let path = dir.join(name.replace('/', "-"));
+        let previous = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| text.trim().parse().ok())
+            .map(Duration::from_nanos);
+        if fs::create_dir_all(dir).is_ok() {
+            let _ = fs::write(&path, median.as_nanos().to_string());
+        }
+        previous
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let nanos = duration.as_nanos() as f64;
+    if nanos < 1e3 {
+        format!("{:.2} ns", nanos)
+    } else if nanos < 1e6 {
+        format!("{:.2} µs", nanos / 1e3)
+    } else if nanos < 1e9 {
+        format!("{:.2} ms", nanos / 1e6)
+    } else {
+        format!("{:.2} s", nanos / 1e9)
+    }
+}
@@ -0,0 +1,91 @@
+//! The programs the benches run: the snippets suite if it's checked out next to the crate, and
+//! a few embedded programs otherwise.
+// Each bench uses only a part of this module.
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::Path;
+
+pub struct Program {
+    /// The path of the snippet without the extension, relative to the snippets suite.
+    pub name: String,
+    pub source: String,
+    /// The contents of the `.in` file next to the snippet.
+    pub input: Vec<u8>,
+}
+
+const EMBEDDED: &[(&str, &str)] = &[
+    (
+        "hello-world",
+        "밤밣따빠밣밟따뿌
+빠맣파빨받밤뚜뭏
+돋밬탕빠맣붏두붇
+볻뫃박발뚷투뭏붖
+뫃도뫃희멓뭏뭏붘
+뫃봌토범더벌뿌뚜
+뽑뽀멓멓더벓뻐뚠
+뽀덩벐멓뻐덕더벅",
+    ),
+    (
+        "fibonacci",
+        "반반나빠빠쌈다빠망빠쌈삼파싸사빠발발밖따따쟈하처우
+ㅇㅇㅇㅇㅇㅇ오어어어어어어어어어어어어어어어어어어",
+    ),
+    // Counts down from 6^6 by 2, which takes about 190000 steps.
+    (
+        "count-down",
+        "붒ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇㅇㅇ
+붒ㅇㅇ희
+뚜아빠추
+ㅇㅇㅇ북
+아오ㅇ터",
+    ),
+];
+
+pub fn programs() -> Vec<Program> {
+    let snippets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../snippets");
+    let mut programs = Vec::new();
+    collect(&snippets, &snippets, &mut programs);
+    if programs.is_empty() {
+        programs.extend(EMBEDDED.iter().map(|&(name, source)| Program {
+            name: name.to_string(),
+            source: source.to_string(),
+            input: Vec::new(),
+        }));
+    }
+    programs.retain(|program| !program.source.trim().is_empty());
+    programs
+}
+
+fn collect(root: &Path, dir: &Path, programs: &mut Vec<Program>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    let mut paths: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            collect(root, &path, programs);
+        } else if path.extension().is_some_and(|ext| ext == "aheui") {
+            let source = match fs::read_to_string(&path) {
+                Ok(source) => source,
+                Err(_) => continue,
+            };
+            let name = path.strip_prefix(root).unwrap().with_extension("");
+            programs.push(Program {
+                name: name.to_string_lossy().into_owned(),
+                source,
+                input: fs::read(path.with_extension("in")).unwrap_or_default(),
+            });
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Runs every program in `programs` to the end with each `Engine`, to measure the throughput.
+//! Run with `cargo bench -p aheui-core --bench snippets [-- NAME...]`, e.g. with `logo` before
+//! and after a change to the interpreter to check that it's not slower.
+
+mod harness;
+mod programs;
+
+use aheui_core::*;
+use harness::Harness;
+
+/// Some snippets never end without the right input.
+const FUEL: u64 = 1_000_000_000;
+
+fn main() {
+    let mut harness = Harness::from_args();
+    for program in programs::programs() {
+        let code = OwnedCode::parse(&program.source);
+        for &(prefix, engine) in &[("", Engine::Interpreter), ("compiled/", Engine::Compiled)] {
+            harness.bench(&format!("{}{}", prefix, program.name), || {
+                let mut input = &program.input[..];
+                let mut output = Vec::new();
+                let mut channel = MemoryChannel::new();
+                let env = Env::new(&code, &mut input, &mut output)
+                    .with_channel(&mut channel)
+                    .with_engine(engine)
+                    .with_fuel(FUEL);
+                let _ = std::hint::black_box(Machine::new(env).run());
+            });
+        }
+    }
+    harness.finish();
+}
@@ -0,0 +1,58 @@
+//! Compares moving the cursor with `Cursor::advance()` and with `TransitionTable`.
+//!
+//! Walks the programs in `programs`, following the arrows of the cells.
+//! Run with `cargo bench -p aheui-core --bench transition`.
+#![feature(test)]
+extern crate test;
+
+mod programs;
+
+use aheui_core::*;
+use test::Bencher;
+
+const STEPS: usize = 10000;
+
+fn programs() -> Vec<OwnedCode> {
+    programs::programs()
+        .iter()
+        .map(|program| OwnedCode::parse(&program.source))
+        .filter(|code| !code.code.is_empty())
+        .collect()
+}
+
+/// Follows the arrows of the cells without executing them.
+#[bench]
+fn bench_cursor_advance(b: &mut Bencher) {
+    let programs = programs();
+    b.iter(|| {
+        for owned in &programs {
+            let code = BorrowedCode::from(owned);
+            let mut cursor = Cursor::new();
+            let mut inst = owned.code[0];
+            for _ in 0..STEPS {
+                inst = cursor.advance(&code, inst.cursor_control, false);
+            }
+            test::black_box((cursor, inst));
+        }
+    });
+}
+
+#[bench]
+fn bench_transition_table(b: &mut Bencher) {
+    let programs = programs();
+    let tables: Vec<_> = programs
+        .iter()
+        .map(|owned| TransitionTable::new(&BorrowedCode::from(owned)))
+        .collect();
+    b.iter(|| {
+        for table in &tables {
+            let mut position = table.position_of(Cursor::new()).unwrap();
+            let mut cursor = Cursor::new();
+            for _ in 0..STEPS {
+                position = table.next(position, false);
+                cursor = table.cursor(position);
+            }
+            test::black_box((cursor, table.inst(position)));
+        }
+    });
+}
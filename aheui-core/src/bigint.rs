@@ -0,0 +1,384 @@
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::Cell;
+
+/// An arbitrary-precision integer.
+#[derive(Clone, Debug, Default, Hash, Eq, PartialEq)]
+pub struct BigInt {
+    negative: bool,
+    /// Little-endian base 2^32 digits without leading zeros.
+    /// Zero has no digits and is never negative.
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    pub fn zero() -> BigInt {
+        BigInt::default()
+    }
+
+    fn from_parts(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        trim(&mut magnitude);
+        BigInt {
+            negative: negative && !magnitude.is_empty(),
+            magnitude,
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    fn neg(&self) -> BigInt {
+        BigInt::from_parts(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl From<i32> for BigInt {
+    fn from(value: i32) -> BigInt {
+        BigInt::from(value as i64)
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> BigInt {
+        let abs = value.unsigned_abs();
+        BigInt::from_parts(value < 0, vec![abs as u32, (abs >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => cmp_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const CHUNK: u32 = 1_000_000_000;
+        let mut chunks = Vec::new();
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_digit(&magnitude, CHUNK);
+            chunks.push(remainder);
+            magnitude = quotient;
+        }
+        let mut digits = match chunks.pop() {
+            Some(chunk) => chunk.to_string(),
+            None => "0".to_string(),
+        };
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{:09}", chunk));
+        }
+        f.pad_integral(!self.negative, "", &digits)
+    }
+}
+
+impl Cell for BigInt {
+    fn from_i32(value: i32) -> Self {
+        BigInt::from(value)
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        let (negative, digits) = match s.as_bytes().first()? {
+            b'-' => (true, &s[1..]),
+            b'+' => (false, &s[1..]),
+            _ => (false, s),
+        };
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = Vec::new();
+        for chunk in digits.as_bytes().chunks(9) {
+            let chunk = std::str::from_utf8(chunk).unwrap();
+            let value = chunk.parse::<u32>().unwrap();
+            magnitude = mul_add_digit(&magnitude, 10u32.pow(chunk.len() as u32), value);
+        }
+        Some(BigInt::from_parts(negative, magnitude))
+    }
+
+    fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    fn to_char(&self) -> Option<char> {
+        match (self.negative, self.magnitude.as_slice()) {
+            (_, []) => Some('\0'),
+            (false, [digit]) => std::char::from_u32(*digit),
+            _ => None,
+        }
+    }
+
+    fn to_i32_wrapping(&self) -> i32 {
+        let low = self.magnitude.first().cloned().unwrap_or(0);
+        if self.negative {
+            low.wrapping_neg() as i32
+        } else {
+            low as i32
+        }
+    }
+
+    fn wrapping_add(&self, rhs: &Self) -> Self {
+        if self.negative == rhs.negative {
+            let magnitude = add_magnitude(&self.magnitude, &rhs.magnitude);
+            return BigInt::from_parts(self.negative, magnitude);
+        }
+        match cmp_magnitude(&self.magnitude, &rhs.magnitude) {
+            Ordering::Equal => BigInt::zero(),
+            Ordering::Greater => {
+                let magnitude = sub_magnitude(&self.magnitude, &rhs.magnitude);
+                BigInt::from_parts(self.negative, magnitude)
+            }
+            Ordering::Less => {
+                let magnitude = sub_magnitude(&rhs.magnitude, &self.magnitude);
+                BigInt::from_parts(rhs.negative, magnitude)
+            }
+        }
+    }
+
+    fn wrapping_sub(&self, rhs: &Self) -> Self {
+        self.wrapping_add(&rhs.neg())
+    }
+
+    fn wrapping_mul(&self, rhs: &Self) -> Self {
+        let magnitude = mul_magnitude(&self.magnitude, &rhs.magnitude);
+        BigInt::from_parts(self.negative != rhs.negative, magnitude)
+    }
+
+    fn wrapping_div(&self, rhs: &Self) -> Self {
+        let (quotient, _) = div_rem_magnitude(&self.magnitude, &rhs.magnitude);
+        BigInt::from_parts(self.negative != rhs.negative, quotient)
+    }
+
+    fn wrapping_rem(&self, rhs: &Self) -> Self {
+        let (_, remainder) = div_rem_magnitude(&self.magnitude, &rhs.magnitude);
+        BigInt::from_parts(self.negative, remainder)
+    }
+
+    // Never overflows.
+
+    fn saturating_add(&self, rhs: &Self) -> Self {
+        self.wrapping_add(rhs)
+    }
+
+    fn saturating_sub(&self, rhs: &Self) -> Self {
+        self.wrapping_sub(rhs)
+    }
+
+    fn saturating_mul(&self, rhs: &Self) -> Self {
+        self.wrapping_mul(rhs)
+    }
+
+    fn saturating_div(&self, rhs: &Self) -> Self {
+        self.wrapping_div(rhs)
+    }
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_add(rhs))
+    }
+
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_sub(rhs))
+    }
+
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_mul(rhs))
+    }
+
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_div(rhs))
+    }
+
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        Some(self.wrapping_rem(rhs))
+    }
+}
+
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+fn cmp_magnitude(a: &[u32], b: &[u32]) -> Ordering {
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (long, short) = if a.len() >= b.len() { (a, b) } else { (b, a) };
+    let mut result = Vec::with_capacity(long.len() + 1);
+    let mut carry = 0u64;
+    for (i, digit) in long.iter().enumerate() {
+        let sum = *digit as u64 + short.get(i).cloned().unwrap_or(0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    result.push(carry as u32);
+    trim(&mut result);
+    result
+}
+
+/// `a` should be greater than or equal to `b`.
+fn sub_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len());
+    let mut borrow = 0i64;
+    for (i, digit) in a.iter().enumerate() {
+        let diff = *digit as i64 - b.get(i).cloned().unwrap_or(0) as i64 - borrow;
+        result.push(diff as u32);
+        borrow = (diff < 0) as i64;
+    }
+    debug_assert_eq!(borrow, 0);
+    trim(&mut result);
+    result
+}
+
+fn mul_magnitude(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut result = vec![0u32; a.len() + b.len()];
+    for (i, x) in a.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in b.iter().enumerate() {
+            let product = *x as u64 * *y as u64 + result[i + j] as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + b.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+/// `a * multiplier + addend`
+fn mul_add_digit(a: &[u32], multiplier: u32, addend: u32) -> Vec<u32> {
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = addend as u64;
+    for digit in a {
+        let product = *digit as u64 * multiplier as u64 + carry;
+        result.push(product as u32);
+        carry = product >> 32;
+    }
+    result.push(carry as u32);
+    trim(&mut result);
+    result
+}
+
+fn div_rem_digit(a: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = 0u64;
+    for i in (0..a.len()).rev() {
+        let dividend = (remainder << 32) | a[i] as u64;
+        quotient[i] = (dividend / divisor as u64) as u32;
+        remainder = dividend % divisor as u64;
+    }
+    trim(&mut quotient);
+    (quotient, remainder as u32)
+}
+
+/// Knuth's Algorithm D. `b` should not be zero.
+fn div_rem_magnitude(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    assert!(!b.is_empty(), "division by zero");
+    if cmp_magnitude(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (quotient, remainder) = div_rem_digit(a, b[0]);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+
+    // Normalize so that the most significant digit of the divisor has its highest bit set.
+    let shift = b.last().unwrap().leading_zeros();
+    let v = shl_bits(b, shift);
+    let mut u = shl_bits(a, shift);
+    u.resize(a.len() + 1, 0);
+    let n = v.len();
+    let m = u.len() - n - 1;
+
+    let mut quotient = vec![0u32; m + 1];
+    for j in (0..=m).rev() {
+        let numerator = ((u[j + n] as u64) << 32) | u[j + n - 1] as u64;
+        let mut q_hat = numerator / v[n - 1] as u64;
+        let mut r_hat = numerator % v[n - 1] as u64;
+        while q_hat >> 32 != 0 || q_hat * v[n - 2] as u64 > ((r_hat << 32) | u[j + n - 2] as u64) {
+            q_hat -= 1;
+            r_hat += v[n - 1] as u64;
+            if r_hat >> 32 != 0 {
+                break;
+            }
+        }
+
+        let mut borrow = 0i64;
+        let mut carry = 0u64;
+        for i in 0..n {
+            let product = q_hat * v[i] as u64 + carry;
+            carry = product >> 32;
+            let diff = u[i + j] as i64 - borrow - (product & 0xffff_ffff) as i64;
+            u[i + j] = diff as u32;
+            borrow = (diff < 0) as i64;
+        }
+        let diff = u[j + n] as i64 - borrow - carry as i64;
+        u[j + n] = diff as u32;
+
+        if diff < 0 {
+            // `q_hat` was one too large. Add the divisor back.
+            q_hat -= 1;
+            let mut carry = 0u64;
+            for i in 0..n {
+                let sum = u[i + j] as u64 + v[i] as u64 + carry;
+                u[i + j] = sum as u32;
+                carry = sum >> 32;
+            }
+            u[j + n] = u[j + n].wrapping_add(carry as u32);
+        }
+        quotient[j] = q_hat as u32;
+    }
+
+    trim(&mut quotient);
+    let mut remainder = shr_bits(&u[..n], shift);
+    trim(&mut remainder);
+    (quotient, remainder)
+}
+
+fn shl_bits(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut result = Vec::with_capacity(a.len() + 1);
+    let mut carry = 0u32;
+    for digit in a {
+        result.push((digit << shift) | carry);
+        carry = digit >> (32 - shift);
+    }
+    if carry != 0 {
+        result.push(carry);
+    }
+    result
+}
+
+fn shr_bits(a: &[u32], shift: u32) -> Vec<u32> {
+    if shift == 0 {
+        return a.to_vec();
+    }
+    let mut result = vec![0u32; a.len()];
+    for i in 0..a.len() {
+        let high = a.get(i + 1).map(|digit| digit << (32 - shift)).unwrap_or(0);
+        result[i] = (a[i] >> shift) | high;
+    }
+    result
+}
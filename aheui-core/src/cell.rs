@@ -0,0 +1,121 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+
+/// The type of the values in the storages.
+///
+/// The arithmetic follows `i32`: the division truncates toward zero,
+/// and the remainder has the sign of the dividend.
+pub trait Cell: Clone + Debug + Display + Hash + Ord {
+    fn from_i32(value: i32) -> Self;
+    /// Parses an integer written in decimal, optionally signed.
+    fn parse(s: &str) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+    fn to_char(&self) -> Option<char>;
+    /// The lowest 32 bits in two's complement, as the exit code of a process.
+    fn to_i32_wrapping(&self) -> i32;
+
+    // `rhs` of the division and the remainder is never zero.
+    fn wrapping_add(&self, rhs: &Self) -> Self;
+    fn wrapping_sub(&self, rhs: &Self) -> Self;
+    fn wrapping_mul(&self, rhs: &Self) -> Self;
+    fn wrapping_div(&self, rhs: &Self) -> Self;
+    fn wrapping_rem(&self, rhs: &Self) -> Self;
+
+    fn saturating_add(&self, rhs: &Self) -> Self;
+    fn saturating_sub(&self, rhs: &Self) -> Self;
+    fn saturating_mul(&self, rhs: &Self) -> Self;
+    fn saturating_div(&self, rhs: &Self) -> Self;
+
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn checked_div(&self, rhs: &Self) -> Option<Self>;
+    fn checked_rem(&self, rhs: &Self) -> Option<Self>;
+}
+
+macro_rules! impl_cell {
+    ($($typ:ty),*) => {
+        $(
+            impl Cell for $typ {
+                fn from_i32(value: i32) -> Self {
+                    value as $typ
+                }
+
+                fn parse(s: &str) -> Option<Self> {
+                    s.parse().ok()
+                }
+
+                fn is_zero(&self) -> bool {
+                    *self == 0
+                }
+
+                fn to_char(&self) -> Option<char> {
+                    u32::try_from(*self).ok().and_then(std::char::from_u32)
+                }
+
+                fn to_i32_wrapping(&self) -> i32 {
+                    *self as i32
+                }
+
+                fn wrapping_add(&self, rhs: &Self) -> Self {
+                    <$typ>::wrapping_add(*self, *rhs)
+                }
+
+                fn wrapping_sub(&self, rhs: &Self) -> Self {
+                    <$typ>::wrapping_sub(*self, *rhs)
+                }
+
+                fn wrapping_mul(&self, rhs: &Self) -> Self {
+                    <$typ>::wrapping_mul(*self, *rhs)
+                }
+
+                fn wrapping_div(&self, rhs: &Self) -> Self {
+                    <$typ>::wrapping_div(*self, *rhs)
+                }
+
+                fn wrapping_rem(&self, rhs: &Self) -> Self {
+                    <$typ>::wrapping_rem(*self, *rhs)
+                }
+
+                fn saturating_add(&self, rhs: &Self) -> Self {
+                    <$typ>::saturating_add(*self, *rhs)
+                }
+
+                fn saturating_sub(&self, rhs: &Self) -> Self {
+                    <$typ>::saturating_sub(*self, *rhs)
+                }
+
+                fn saturating_mul(&self, rhs: &Self) -> Self {
+                    <$typ>::saturating_mul(*self, *rhs)
+                }
+
+                fn saturating_div(&self, rhs: &Self) -> Self {
+                    <$typ>::saturating_div(*self, *rhs)
+                }
+
+                fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                    <$typ>::checked_add(*self, *rhs)
+                }
+
+                fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+                    <$typ>::checked_sub(*self, *rhs)
+                }
+
+                fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                    <$typ>::checked_mul(*self, *rhs)
+                }
+
+                fn checked_div(&self, rhs: &Self) -> Option<Self> {
+                    <$typ>::checked_div(*self, *rhs)
+                }
+
+                fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+                    <$typ>::checked_rem(*self, *rhs)
+                }
+            }
+        )*
+    };
+}
+
+impl_cell!(i32, i64, i128);
@@ -0,0 +1,188 @@
+use crate::{arithmetic, ArithmeticMode, Cell, DivZeroPolicy, Oper, TransitionTable};
+
+/// Instructions in a block at most.
+const MAX_BLOCK_STEPS: u64 = 64;
+
+/// An operation of a block on the selected stack.
+#[derive(Clone, Debug)]
+enum Op<T> {
+    Push(T),
+    Dup,
+    Swap,
+    Pop,
+    /// `Add`, `Sub`, `Mul` or `Compare` of the two values on the top.
+    Binary(Oper),
+    /// `Push(value)` and `Binary(oper)`: replaces the top `x` with `x (oper) value`.
+    BinaryConst(Oper, T),
+    /// `Dup` and `Binary(oper)`: replaces the top `x` with `x (oper) x`.
+    DupBinary(Oper),
+}
+
+/// A straight-line run of instructions, which can't reverse the cursor or fail while the
+/// selected storage is a stack with `depth` values or more.
+pub(crate) struct Block<T> {
+    ops: Vec<Op<T>>,
+    depth: usize,
+    mode: ArithmeticMode,
+    /// The number of the instructions. An empty block means that the first instruction has to
+    /// be executed by the interpreter.
+    pub(crate) steps: u64,
+    /// The position of the cursor after the block.
+    pub(crate) end: usize,
+}
+
+impl<T: Cell> Block<T> {
+    fn compile(table: &TransitionTable, start: usize, mode: ArithmeticMode) -> Block<T> {
+        let mut block = Block {
+            ops: Vec::new(),
+            depth: 0,
+            mode,
+            steps: 0,
+            end: start,
+        };
+        let mut visited = Vec::new();
+        // The height of the stack relative to the start.
+        let mut height = 0isize;
+        while block.steps < MAX_BLOCK_STEPS && !visited.contains(&block.end) {
+            let oper = table.inst(block.end).oper;
+            let (pops, pushes) = match oper {
+                Oper::Nop => (0, 0),
+                Oper::Push(_) => (0, 1),
+                Oper::Dup => (1, 2),
+                Oper::Swap => (2, 2),
+                Oper::Pop => (1, 0),
+                // They may overflow.
+                Oper::Add | Oper::Sub | Oper::Mul if mode == ArithmeticMode::Trap => break,
+                Oper::Add | Oper::Sub | Oper::Mul | Oper::Compare => (2, 1),
+                _ => break,
+            };
+            block.depth = block.depth.max((pops - height).max(0) as usize);
+            height += pushes - pops;
+            block.push_op(oper);
+            block.steps += 1;
+            visited.push(block.end);
+            block.end = table.next(block.end, false);
+        }
+        block
+    }
+
+    /// Appends the operation of `oper`, folding it with the last ones if possible.
+    ///
+    /// The rewrites are only valid with enough values on the stack, which `depth` ensures by
+    /// the instructions as they were.
+    fn push_op(&mut self, oper: Oper) {
+        let ops = &mut self.ops;
+        let op = match (oper, ops.last()) {
+            (Oper::Nop, _) => return,
+            (Oper::Push(value), _) => Op::Push(T::from_i32(value as i32)),
+            (Oper::Dup, Some(Op::Push(value))) => Op::Push(value.clone()),
+            (Oper::Dup, _) => Op::Dup,
+            (Oper::Pop, Some(Op::Push(_))) | (Oper::Pop, Some(Op::Dup)) => {
+                ops.pop();
+                return;
+            }
+            (Oper::Pop, _) => Op::Pop,
+            (Oper::Swap, Some(Op::Swap)) => {
+                ops.pop();
+                return;
+            }
+            (Oper::Swap, Some(Op::Push(_))) => match ops.as_mut_slice() {
+                [.., Op::Push(a), Op::Push(b)] => {
+                    std::mem::swap(a, b);
+                    return;
+                }
+                _ => Op::Swap,
+            },
+            (Oper::Swap, _) => Op::Swap,
+            (_, Some(Op::Push(value))) => {
+                let value = value.clone();
+                ops.pop();
+                match ops.last() {
+                    Some(Op::Push(second)) => {
+                        let result = eval(self.mode, oper, &value, second);
+                        ops.pop();
+                        Op::Push(result)
+                    }
+                    _ => Op::BinaryConst(oper, value),
+                }
+            }
+            (_, Some(Op::Dup)) => {
+                ops.pop();
+                Op::DupBinary(oper)
+            }
+            _ => Op::Binary(oper),
+        };
+        ops.push(op);
+    }
+
+    /// Executes the block on `stack` if it has enough values. Returns `false` otherwise.
+    pub(crate) fn run(&self, stack: &mut Vec<T>) -> bool {
+        if self.steps == 0 || stack.len() < self.depth {
+            return false;
+        }
+        for op in &self.ops {
+            match op {
+                Op::Push(value) => stack.push(value.clone()),
+                Op::Dup => stack.push(stack[stack.len() - 1].clone()),
+                Op::Swap => {
+                    let len = stack.len();
+                    stack.swap(len - 1, len - 2);
+                }
+                Op::Pop => {
+                    stack.pop();
+                }
+                Op::Binary(oper) => {
+                    let first = stack.pop().unwrap();
+                    let second = stack.last_mut().unwrap();
+                    *second = eval(self.mode, *oper, &first, second);
+                }
+                Op::BinaryConst(oper, value) => {
+                    let top = stack.last_mut().unwrap();
+                    *top = eval(self.mode, *oper, value, top);
+                }
+                Op::DupBinary(oper) => {
+                    let top = stack.last_mut().unwrap();
+                    *top = eval(self.mode, *oper, top, top);
+                }
+            }
+        }
+        true
+    }
+}
+
+/// Calculates `oper` of the values that would be popped first and second, which can't fail.
+fn eval<T: Cell>(mode: ArithmeticMode, oper: Oper, first: &T, second: &T) -> T {
+    match oper {
+        Oper::Compare => T::from_i32((first <= second) as i32),
+        _ => match arithmetic(
+            mode,
+            DivZeroPolicy::Error,
+            oper,
+            second.clone(),
+            first.clone(),
+        ) {
+            Ok(Some(value)) => value,
+            _ => unreachable!("{:?} has failed in a block", oper),
+        },
+    }
+}
+
+/// The blocks starting from each position of a `TransitionTable`, compiled on the first use.
+pub(crate) struct Blocks<T> {
+    mode: ArithmeticMode,
+    blocks: Vec<Option<Block<T>>>,
+}
+
+impl<T: Cell> Blocks<T> {
+    pub(crate) fn new(table: &TransitionTable, mode: ArithmeticMode) -> Self {
+        Blocks {
+            mode,
+            blocks: (0..table.positions()).map(|_| None).collect(),
+        }
+    }
+
+    pub(crate) fn get(&mut self, table: &TransitionTable, position: usize) -> &Block<T> {
+        let mode = self.mode;
+        self.blocks[position].get_or_insert_with(|| Block::compile(table, position, mode))
+    }
+}
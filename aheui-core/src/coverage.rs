@@ -0,0 +1,187 @@
+use crate::{Address, BorrowedCode, Cell, CursorControl, Inst, ObservedStep, Observer, Oper, Step};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct BranchCount {
+    /// Executions after which the cursor went on as the instruction says.
+    pub forward: u64,
+    /// Executions after which the cursor was reversed.
+    pub reversed: u64,
+}
+
+impl BranchCount {
+    pub fn total(&self) -> u64 {
+        self.forward + self.reversed
+    }
+
+    fn merge(&mut self, other: BranchCount) {
+        self.forward += other.forward;
+        self.reversed += other.reversed;
+    }
+}
+
+/// An observer that records which cells were executed, from which direction, and whether the
+/// cursor was reversed afterwards. Coverages of multiple runs of the same code can be merged.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Coverage {
+    hits: HashMap<(Address, Step), BranchCount>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the counts of `other`, which should have been collected from the same code.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (&key, &count) in &other.hits {
+            self.hits.entry(key).or_default().merge(count);
+        }
+    }
+
+    /// Counts of the executions of `address` entered in `direction`.
+    pub fn hits(&self, address: Address, direction: Step) -> BranchCount {
+        self.hits
+            .get(&(address, direction))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Counts of the executions of `address` from all directions.
+    pub fn cell(&self, address: Address) -> BranchCount {
+        let mut result = BranchCount::default();
+        for (_, &count) in self.hits.iter().filter(|((a, _), _)| *a == address) {
+            result.merge(count);
+        }
+        result
+    }
+
+    /// Finds the cells of `code` that were never executed, and `Oper::Cond` cells that only
+    /// went one way. Cells that do nothing at all, such as spaces and `ㅇ` fillers, are ignored.
+    pub fn report<'c, C: Into<BorrowedCode<'c>>>(&self, code: C) -> CoverageReport {
+        let code = code.into();
+        let mut cells = HashMap::<Address, BranchCount>::new();
+        for (&(address, _), &count) in &self.hits {
+            cells.entry(address).or_default().merge(count);
+        }
+
+        let mut report = CoverageReport::default();
+        for (row, range) in code.index.windows(2).enumerate() {
+            for (col, inst) in code.code[range[0]..range[1]].iter().enumerate() {
+                if is_blank(inst) {
+                    continue;
+                }
+                let address = Address {
+                    row: row as i32,
+                    col: col as i32,
+                };
+                report.cells += 1;
+                let count = cells.get(&address).copied().unwrap_or_default();
+                if count.total() == 0 {
+                    report.uncovered.push(address);
+                    continue;
+                }
+                report.covered += 1;
+                if let Oper::Cond = inst.oper {
+                    if count.forward == 0 || count.reversed == 0 {
+                        report.one_sided.push((address, count));
+                    }
+                }
+            }
+        }
+        report
+    }
+}
+
+/// Coverage merged from every run of the same code, which can be kept in a `static`.
+/// `#[아희(커버리지 = NAME)]` declares one as `NAME` for the code of the function.
+pub struct SharedCoverage {
+    code: BorrowedCode<'static>,
+    coverage: Mutex<Option<Coverage>>,
+}
+
+impl SharedCoverage {
+    pub const fn new(code: BorrowedCode<'static>) -> Self {
+        SharedCoverage {
+            code,
+            coverage: Mutex::new(None),
+        }
+    }
+
+    pub fn code(&self) -> BorrowedCode<'static> {
+        self.code
+    }
+
+    /// Adds the counts of `coverage`, which should have been collected from `code()`.
+    pub fn merge(&self, coverage: &Coverage) {
+        let mut shared = self.coverage.lock().unwrap_or_else(|e| e.into_inner());
+        shared.get_or_insert_with(Coverage::new).merge(coverage);
+    }
+
+    /// The counts merged so far.
+    pub fn coverage(&self) -> Coverage {
+        let shared = self.coverage.lock().unwrap_or_else(|e| e.into_inner());
+        shared.clone().unwrap_or_default()
+    }
+
+    pub fn report(&self) -> CoverageReport {
+        self.coverage().report(self.code())
+    }
+}
+
+fn is_blank(inst: &Inst) -> bool {
+    matches!(
+        (inst.cursor_control, inst.oper),
+        (CursorControl::Nop, Oper::Nop)
+    )
+}
+
+impl<T: Cell> Observer<T> for Coverage {
+    fn after_step(&mut self, step: &ObservedStep<'_, T>) {
+        let count = self.hits.entry((step.address, step.direction)).or_default();
+        if step.reversed {
+            count.reversed += 1;
+        } else {
+            count.forward += 1;
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct CoverageReport {
+    /// The number of cells that do something.
+    pub cells: usize,
+    pub covered: usize,
+    /// Cells never executed, in the reading order.
+    pub uncovered: Vec<Address>,
+    /// `Oper::Cond` cells that have never been reversed, or have always been reversed.
+    pub one_sided: Vec<(Address, BranchCount)>,
+}
+
+impl fmt::Display for CoverageReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "covered {} of {} cells", self.covered, self.cells)?;
+        for address in &self.uncovered {
+            writeln!(
+                f,
+                "never executed: row {}, column {}",
+                address.row, address.col
+            )?;
+        }
+        for (address, count) in &self.one_sided {
+            let taken = if count.reversed == 0 {
+                "never reversed"
+            } else {
+                "always reversed"
+            };
+            writeln!(
+                f,
+                "one-sided condition: row {}, column {}, {}",
+                address.row, address.col, taken
+            )?;
+        }
+        Ok(())
+    }
+}
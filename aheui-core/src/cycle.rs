@@ -0,0 +1,54 @@
+use crate::{Cell, Cursor, StorageSelector, StorageSnapshot};
+
+/// Finds a repeated state of the machine with Brent's algorithm: the state is saved at every
+/// power of two steps and compared with the following states. The storages are hashed only when
+/// the cursor and the lengths of the storages match, and compared in full when the hashes match.
+pub(crate) struct LoopDetector<T> {
+    power: u64,
+    /// Steps since the state is saved.
+    distance: u64,
+    saved: Option<(Cursor, u64, StorageSnapshot<T>)>,
+}
+
+impl<T: Cell> LoopDetector<T> {
+    pub(crate) fn new() -> Self {
+        LoopDetector {
+            power: 1,
+            distance: 0,
+            saved: None,
+        }
+    }
+
+    /// Forgets the saved state after the machine does something that isn't determined by its
+    /// own state, such as reading the input.
+    pub(crate) fn reset(&mut self) {
+        self.power = 1;
+        self.distance = 0;
+        self.saved = None;
+    }
+
+    /// Returns the length of the cycle if the state has been seen before.
+    pub(crate) fn check(
+        &mut self,
+        cursor: Cursor,
+        storages: &StorageSelector<'_, T>,
+    ) -> Option<u64> {
+        if let Some((saved_cursor, saved_hash, saved_storages)) = &self.saved {
+            self.distance += 1;
+            if *saved_cursor == cursor
+                && storages.same_lengths(saved_storages)
+                && *saved_hash == storages.state_hash()
+                && storages.matches(saved_storages)
+            {
+                return Some(self.distance);
+            }
+            if self.distance < self.power {
+                return None;
+            }
+            self.power *= 2;
+        }
+        self.distance = 0;
+        self.saved = Some((cursor, storages.state_hash(), storages.snapshot()));
+        None
+    }
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use crate::{Address, Cell, Cursor, Oper};
+
+#[derive(Debug)]
+pub struct ExecError<T = i32> {
+    pub address: Address,
+    pub kind: ExecErrorKind<T>,
+}
+
+#[derive(Debug)]
+pub enum ExecErrorKind<T = i32> {
+    Io(std::io::Error),
+    UnexpectedEof,
+    InvalidUtf8,
+    MalformedInt(String),
+    InvalidCodePoint(T),
+    DivisionByZero,
+    Overflow { oper: Oper, lhs: T, rhs: T },
+    NoChannel,
+}
+
+impl<T> ExecError<T> {
+    pub fn new(address: Address, kind: ExecErrorKind<T>) -> ExecError<T> {
+        ExecError { address, kind }
+    }
+}
+
+impl<T: Cell> fmt::Display for ExecError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at row {}, column {}",
+            self.kind, self.address.row, self.address.col
+        )
+    }
+}
+
+impl<T: Cell> fmt::Display for ExecErrorKind<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ExecErrorKind::Io(e) => write!(f, "I/O error: {}", e),
+            ExecErrorKind::UnexpectedEof => write!(f, "unexpected end of the input"),
+            ExecErrorKind::InvalidUtf8 => write!(f, "invalid UTF-8 sequence in the input"),
+            ExecErrorKind::MalformedInt(line) => write!(f, "malformed integer {:?}", line),
+            ExecErrorKind::InvalidCodePoint(value) => {
+                write!(f, "{} is not a valid unicode code point", value)
+            }
+            ExecErrorKind::DivisionByZero => write!(f, "division by zero"),
+            ExecErrorKind::Overflow { oper, lhs, rhs } => {
+                write!(f, "overflow in {:?} of {} and {}", oper, lhs, rhs)
+            }
+            ExecErrorKind::NoChannel => write!(f, "no channel is configured for the ㅎ storage"),
+        }
+    }
+}
+
+impl<T: Cell> std::error::Error for ExecError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.kind {
+            ExecErrorKind::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<T> From<std::io::Error> for ExecErrorKind<T> {
+    fn from(e: std::io::Error) -> ExecErrorKind<T> {
+        ExecErrorKind::Io(e)
+    }
+}
+
+/// An error from parsing the text of `MachineState`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseStateError {
+    /// 1-based line number, or 0 if the error is not about a line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl ParseStateError {
+    pub fn new(line: usize, message: impl Into<String>) -> ParseStateError {
+        ParseStateError {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ParseStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "invalid machine state: {}", self.message)
+        } else {
+            write!(
+                f,
+                "invalid machine state at line {}: {}",
+                self.line, self.message
+            )
+        }
+    }
+}
+
+impl std::error::Error for ParseStateError {}
+
+/// An error from restoring a `MachineState` that doesn't fit the machine.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum RestoreError {
+    /// The cursor is out of the code, or moves by other than 1 or 2 cells.
+    InvalidCursor(Cursor),
+    /// The state selects a stack that doesn't exist.
+    InvalidStack(u8),
+    /// The state has the given number of stacks instead of 28.
+    StackCount(usize),
+    /// The state selects the channel, but the machine has no channel.
+    NoChannel,
+}
+
+impl fmt::Display for RestoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RestoreError::InvalidCursor(cursor) => write!(
+                f,
+                "the cursor at row {}, column {} moving {:?} is not on the code",
+                cursor.address.row, cursor.address.col, cursor.step
+            ),
+            RestoreError::InvalidStack(id) => write!(f, "there's no stack {}", id),
+            RestoreError::StackCount(count) => {
+                write!(f, "the state has {} stacks instead of 28", count)
+            }
+            RestoreError::NoChannel => write!(f, "no channel is configured for the ㅎ storage"),
+        }
+    }
+}
+
+impl std::error::Error for RestoreError {}
@@ -0,0 +1,107 @@
+use crate::MachineState;
+use std::collections::VecDeque;
+
+/// What `Machine` records to go back in time: the states at every `interval` steps, and the
+/// values read from the input since the oldest of them. Going back to a step restores the
+/// closest state before it and re-executes the steps in between with the recorded input.
+pub(crate) struct History<T> {
+    interval: u64,
+    max_checkpoints: usize,
+    max_cells: usize,
+    checkpoints: VecDeque<MachineState<T>>,
+    /// The number of values stored in `checkpoints`.
+    cells: usize,
+    /// Values read by each step, or `None` at the end of the input, and the bytes read so far
+    /// after the step.
+    reads: VecDeque<(u64, Option<T>, u64)>,
+    /// The number of steps executed for the first time. Steps before it are re-executed.
+    pub(crate) frontier: u64,
+}
+
+impl<T: Clone> History<T> {
+    pub(crate) fn new(interval: u64, max_checkpoints: usize, max_cells: usize) -> Self {
+        History {
+            interval: interval.max(1),
+            max_checkpoints: max_checkpoints.max(1),
+            max_cells,
+            checkpoints: VecDeque::new(),
+            cells: 0,
+            reads: VecDeque::new(),
+            frontier: 0,
+        }
+    }
+
+    pub(crate) fn is_replaying(&self, steps: u64) -> bool {
+        steps < self.frontier
+    }
+
+    /// Whether the state before executing the step `steps` should be saved.
+    pub(crate) fn needs_checkpoint(&self, steps: u64) -> bool {
+        steps >= self.frontier
+            && steps.is_multiple_of(self.interval)
+            && self.checkpoints.back().map(|c| c.steps) != Some(steps)
+    }
+
+    pub(crate) fn push_checkpoint(&mut self, state: MachineState<T>) {
+        self.cells += cells(&state);
+        self.checkpoints.push_back(state);
+        if self.checkpoints.len() <= self.max_checkpoints && self.cells <= self.max_cells {
+            return;
+        }
+        while self.checkpoints.len() > 1
+            && (self.checkpoints.len() > self.max_checkpoints || self.cells > self.max_cells)
+        {
+            let oldest = self.checkpoints.pop_front().unwrap();
+            self.cells -= cells(&oldest);
+        }
+        let oldest = self.checkpoints[0].steps;
+        while self
+            .reads
+            .front()
+            .is_some_and(|&(step, _, _)| step < oldest)
+        {
+            self.reads.pop_front();
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.checkpoints.clear();
+        self.cells = 0;
+        self.reads.clear();
+        self.frontier = 0;
+    }
+
+    /// Forgets everything before `state`, so that it can't be undone. The channel can't be
+    /// restored, so this is done after every step that touches it.
+    pub(crate) fn restart(&mut self, state: MachineState<T>) {
+        self.checkpoints.clear();
+        self.reads.retain(|&(step, _, _)| step >= state.steps);
+        self.frontier = self.frontier.max(state.steps);
+        self.cells = cells(&state);
+        self.checkpoints.push_back(state);
+    }
+
+    pub(crate) fn record_read(&mut self, step: u64, value: Option<T>, read: u64) {
+        self.reads.push_back((step, value, read));
+    }
+
+    pub(crate) fn recorded_read(&self, step: u64) -> Option<(Option<T>, u64)> {
+        let index = self
+            .reads
+            .binary_search_by_key(&step, |&(step, _, _)| step)
+            .ok()?;
+        let (_, value, read) = &self.reads[index];
+        Some((value.clone(), *read))
+    }
+
+    /// The latest checkpoint at or before `steps`.
+    pub(crate) fn checkpoint_before(&self, steps: u64) -> Option<&MachineState<T>> {
+        self.checkpoints.iter().rev().find(|c| c.steps <= steps)
+    }
+}
+
+/// The number of values stored in `state`.
+fn cells<T>(state: &MachineState<T>) -> usize {
+    let storages = &state.storages;
+    storages.stacks.iter().map(Vec::len).sum::<usize>() + storages.queue.len()
+}
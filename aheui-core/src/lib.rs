@@ -1,7 +1,40 @@
 use std::collections::VecDeque;
 #[cfg(feature = "render")]
 use std::fmt::Write as FmtWrite;
+use std::hash::{Hash, Hasher};
 use std::io::{BufRead, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[cfg(feature = "bigint")]
+mod bigint;
+mod cell;
+mod compile;
+mod coverage;
+mod cycle;
+mod error;
+mod history;
+#[doc(hidden)]
+pub mod native;
+mod observer;
+mod profile;
+mod state;
+mod transition;
+
+#[cfg(feature = "bigint")]
+pub use bigint::BigInt;
+pub use cell::Cell;
+use compile::Blocks;
+pub use coverage::{BranchCount, Coverage, CoverageReport, SharedCoverage};
+use cycle::LoopDetector;
+pub use error::*;
+use history::History;
+use observer::StepLog;
+pub use observer::{ObservedStep, Observer};
+pub use profile::{CellProfile, Profiler};
+pub use state::MachineState;
+pub use transition::TransitionTable;
 
 #[cfg(feature = "render")]
 pub trait Render {
@@ -15,7 +48,7 @@ pub struct Inst {
 }
 
 impl Inst {
-    pub fn new(cursor_control: CursorControl, oper: Oper) -> Inst {
+    pub const fn new(cursor_control: CursorControl, oper: Oper) -> Inst {
         Inst {
             cursor_control,
             oper,
@@ -26,7 +59,7 @@ impl Inst {
 #[cfg(feature = "parse")]
 impl std::convert::From<char> for Inst {
     fn from(ch: char) -> Inst {
-        if ch < '\u{AC00}' || ch > '\u{D7A3}' {
+        if !('\u{AC00}'..='\u{D7A3}').contains(&ch) {
             return Inst::new(CursorControl::Nop, Oper::Nop);
         }
         let index = ch as u32 - 0xAC00;
@@ -190,11 +223,34 @@ pub enum Oper {
     Cond,
 }
 
-#[cfg(feature = "render")]
-impl Render for Oper {
-    fn render(&self, prefix: &str) -> String {
+impl Oper {
+    /// Every name that `name()` returns.
+    pub const NAMES: [&'static str; 19] = [
+        "Nop",
+        "Halt",
+        "Add",
+        "Mul",
+        "Sub",
+        "Div",
+        "Mod",
+        "WriteChar",
+        "WriteInt",
+        "Pop",
+        "ReadChar",
+        "ReadInt",
+        "Push",
+        "Dup",
+        "Swap",
+        "Select",
+        "Move",
+        "Compare",
+        "Cond",
+    ];
+
+    /// The name of the variant, without the operand.
+    pub fn name(&self) -> &'static str {
         use Oper::*;
-        let variant_name = match self {
+        match self {
             Nop => "Nop",
             Halt => "Halt",
             Add => "Add",
@@ -214,7 +270,15 @@ impl Render for Oper {
             Move(_) => "Move",
             Compare => "Compare",
             Cond => "Cond",
-        };
+        }
+    }
+}
+
+#[cfg(feature = "render")]
+impl Render for Oper {
+    fn render(&self, prefix: &str) -> String {
+        use Oper::*;
+        let variant_name = self.name();
         match self {
             Push(value) => format!("{}Oper::{}({})", prefix, variant_name, value),
             Select(storage) | Move(storage) => format!(
@@ -228,7 +292,7 @@ impl Render for Oper {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub enum Select {
     Stack(u8),
     Queue,
@@ -315,6 +379,7 @@ impl<'a> From<&'a OwnedCode> for BorrowedCode<'a> {
     }
 }
 
+#[derive(Copy, Clone)]
 pub struct BorrowedCode<'a> {
     pub index: &'a [usize],
     pub code: &'a [Inst],
@@ -414,6 +479,12 @@ impl Cursor {
     }
 }
 
+impl Default for Cursor {
+    fn default() -> Cursor {
+        Cursor::new()
+    }
+}
+
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
 pub struct Address {
     pub row: i32,
@@ -466,14 +537,40 @@ impl Step {
     }
 }
 
-pub struct Env<'a> {
+pub struct Env<'a, T: Cell = i32> {
     code: BorrowedCode<'a>,
     input: &'a mut dyn BufRead,
     output: &'a mut dyn Write,
+    channel: Option<&'a mut dyn Channel<T>>,
+    observer: Option<&'a mut dyn Observer<T>>,
+    eof_policy: EofPolicy,
+    arithmetic_mode: ArithmeticMode,
+    div_zero_policy: DivZeroPolicy,
+    engine: Engine,
+    fuel: Option<u64>,
+    cell_limit: Option<usize>,
+    output_limit: Option<u64>,
+    cancel_handle: Option<CancelHandle>,
+    deadline: Option<Instant>,
+    history: Option<(u64, usize)>,
+    history_cell_limit: Option<usize>,
+    loop_detection: bool,
+    /// Bytes read from `input`.
+    read: u64,
 }
 
 impl<'a> Env<'a> {
     pub fn new<C>(code: C, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self
+    where
+        C: Into<BorrowedCode<'a>>,
+    {
+        Env::new_with_cell(code, input, output)
+    }
+}
+
+impl<'a, T: Cell> Env<'a, T> {
+    /// Same as `Env::new()`, but the storages hold `T` instead of `i32`.
+    pub fn new_with_cell<C>(code: C, input: &'a mut dyn BufRead, output: &'a mut dyn Write) -> Self
     where
         C: Into<BorrowedCode<'a>>,
     {
@@ -481,209 +578,1298 @@ impl<'a> Env<'a> {
             code: code.into(),
             input,
             output,
+            channel: None,
+            observer: None,
+            eof_policy: EofPolicy::default(),
+            arithmetic_mode: ArithmeticMode::default(),
+            div_zero_policy: DivZeroPolicy::default(),
+            engine: Engine::default(),
+            fuel: None,
+            cell_limit: None,
+            output_limit: None,
+            cancel_handle: None,
+            deadline: None,
+            history: None,
+            history_cell_limit: None,
+            loop_detection: false,
+            read: 0,
         }
     }
+
+    /// Provides the storage selected by `ㅎ` batchim.
+    pub fn with_channel(mut self, channel: &'a mut dyn Channel<T>) -> Self {
+        self.channel = Some(channel);
+        self
+    }
+
+    /// Reports every executed instruction and I/O to `observer`.
+    pub fn with_observer(mut self, observer: &'a mut dyn Observer<T>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn with_eof_policy(mut self, eof_policy: EofPolicy) -> Self {
+        self.eof_policy = eof_policy;
+        self
+    }
+
+    pub fn with_arithmetic_mode(mut self, arithmetic_mode: ArithmeticMode) -> Self {
+        self.arithmetic_mode = arithmetic_mode;
+        self
+    }
+
+    pub fn with_div_zero_policy(mut self, div_zero_policy: DivZeroPolicy) -> Self {
+        self.div_zero_policy = div_zero_policy;
+        self
+    }
+
+    pub fn with_engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    /// Stops the execution with `Outcome::FuelExhausted` after executing `steps` instructions.
+    pub fn with_fuel(mut self, steps: u64) -> Self {
+        self.fuel = Some(steps);
+        self
+    }
+
+    /// Limits the number of values stored in the stacks and the queue at once.
+    /// The channel doesn't count.
+    pub fn with_cell_limit(mut self, cells: usize) -> Self {
+        self.cell_limit = Some(cells);
+        self
+    }
+
+    /// Limits the number of bytes written to `output`.
+    pub fn with_output_limit(mut self, bytes: u64) -> Self {
+        self.output_limit = Some(bytes);
+        self
+    }
+
+    /// Stops the execution with `Outcome::Cancelled` once `handle` is cancelled.
+    pub fn with_cancel_handle(mut self, handle: CancelHandle) -> Self {
+        self.cancel_handle = Some(handle);
+        self
+    }
+
+    /// Stops the execution with `Outcome::TimedOut` once `deadline` has passed.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Records the execution so that `Machine::step_back()` can undo steps, saving the state
+    /// every `interval` steps and keeping at most `max_checkpoints` of them.
+    ///
+    /// Steps that pop or push values of the channel can't be undone.
+    pub fn with_history(mut self, interval: u64, max_checkpoints: usize) -> Self {
+        self.history = Some((interval, max_checkpoints));
+        self
+    }
+
+    /// Limits the number of values kept in the checkpoints of the history in total, forgetting
+    /// the oldest checkpoints beyond it. The latest checkpoint is always kept.
+    pub fn with_history_cell_limit(mut self, cells: usize) -> Self {
+        self.history_cell_limit = Some(cells);
+        self
+    }
+
+    /// Stops the execution with `Outcome::InfiniteLoop` when the cursor and the storages come
+    /// back to the same state without reading the input or touching the channel in between.
+    pub fn with_loop_detection(mut self) -> Self {
+        self.loop_detection = true;
+        self
+    }
 }
 
-impl<'a> Env<'a> {
-    pub fn execute(mut self) -> i32 {
-        let mut cursor = Cursor::new();
-        let mut storages = StorageSelector::new();
-        let mut inst = self.code.get_inst(cursor.address).unwrap();
+/// What `ReadChar` and `ReadInt` do when the input is exhausted.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum EofPolicy {
+    /// Pushes -1, as most of the other implementations do.
+    #[default]
+    PushNegativeOne,
+    /// Pushes nothing and reverses the cursor.
+    Reverse,
+    /// Stops the execution with `ExecErrorKind::UnexpectedEof`.
+    Error,
+}
+
+/// What the arithmetic instructions do when the result doesn't fit in a cell.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum ArithmeticMode {
+    /// Wraps around in two's complement.
+    #[default]
+    Wrap,
+    /// Clamps to the minimum or the maximum value of the cell.
+    Saturate,
+    /// Stops the execution with `ExecErrorKind::Overflow`.
+    Trap,
+}
+
+/// What `Div` and `Mod` do when the divisor is zero.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum DivZeroPolicy {
+    /// Stops the execution with `ExecErrorKind::DivisionByZero`.
+    #[default]
+    Error,
+    /// Leaves the operands in the storage and reverses the cursor.
+    Reverse,
+    /// Pops the operands and pushes 0.
+    PushZero,
+}
+
+/// How `Machine::run()` executes the instructions. The results are the same.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum Engine {
+    /// Executes the instructions one by one.
+    #[default]
+    Interpreter,
+    /// Compiles straight-line runs of stack operations and arithmetic into blocks, with
+    /// constants folded and common sequences fused, and executes a block at once when the
+    /// selected stack has enough values for it. Only used without an observer, a history,
+    /// limits, or loop detection, like the fast path of the interpreter.
+    Compiled,
+}
+
+impl<'a, T: Cell> Env<'a, T> {
+    /// Runs the program to the end. Panics on an execution error, see `try_execute()`.
+    pub fn execute(self) -> T {
+        match self.try_execute() {
+            Ok(Outcome::Halted(value)) => value,
+            Ok(outcome) => panic!("the execution has stopped: {:?}", outcome),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_execute(self) -> Result<Outcome<T>, ExecError<T>> {
+        Machine::new(self).run()
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome<T = i32> {
+    Halted(T),
+    /// The step budget given by `Env::with_fuel()` has run out before halting.
+    FuelExhausted {
+        steps: u64,
+        cursor: Cursor,
+        storages: StorageSnapshot<T>,
+    },
+    /// The next instruction would have exceeded a limit given to `Env`, so it wasn't executed.
+    LimitExceeded {
+        limit: Limit,
+        steps: u64,
+        cursor: Cursor,
+        storages: StorageSnapshot<T>,
+    },
+    /// The handle given by `Env::with_cancel_handle()` has been cancelled.
+    Cancelled {
+        steps: u64,
+        cursor: Cursor,
+        storages: StorageSnapshot<T>,
+    },
+    /// The deadline given by `Env::with_deadline()` has passed.
+    TimedOut {
+        steps: u64,
+        cursor: Cursor,
+        storages: StorageSnapshot<T>,
+    },
+    /// The state has repeated, so it would never halt. Enabled by `Env::with_loop_detection()`.
+    InfiniteLoop {
+        steps: u64,
+        cursor: Cursor,
+        storages: StorageSnapshot<T>,
+        /// Addresses executed in one round of the loop, starting from `cursor`.
+        cycle: Vec<Address>,
+    },
+}
+
+/// Cancellation and the deadline are checked once per this many steps.
+const INTERRUPT_CHECK_INTERVAL: u64 = 1024;
+
+/// A shared flag to cancel a running `Env` from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Limit {
+    /// Set by `Env::with_cell_limit()`.
+    Cells,
+    /// Set by `Env::with_output_limit()`.
+    Output,
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum StepEvent<T = i32> {
+    Executed {
+        address: Address,
+        inst: Inst,
+        reversed: bool,
+    },
+    Halted {
+        address: Address,
+        value: T,
+    },
+    LimitExceeded {
+        address: Address,
+        limit: Limit,
+    },
+}
+
+pub struct Machine<'a, T: Cell = i32> {
+    env: Env<'a, T>,
+    cursor: Cursor,
+    /// The position of `cursor` in `transitions`.
+    position: usize,
+    inst: Inst,
+    transitions: TransitionTable,
+    storages: StorageSelector<'a, T>,
+    halted: Option<T>,
+    steps: u64,
+    written: u64,
+    observer: Option<&'a mut dyn Observer<T>>,
+    log: StepLog<T>,
+    history: Option<History<T>>,
+    /// Whether the current step is re-executed after going back, which doesn't read the input,
+    /// write the output, or notify the observer.
+    replaying: bool,
+    loop_detector: Option<LoopDetector<T>>,
+    blocks: Option<Blocks<T>>,
+    /// Whether the steps are only to be undone, as `replaying` but without the history.
+    dry_run: bool,
+}
+
+impl<'a, T: Cell> Machine<'a, T> {
+    pub fn new(mut env: Env<'a, T>) -> Self {
+        let cursor = Cursor::new();
+        let transitions = TransitionTable::new(&env.code);
+        // Without an instruction at the start, e.g. for empty code, it halts before any step
+        // as if it had executed `희` on the empty stack.
+        let (position, inst, halted) = match transitions.position_of(cursor) {
+            Some(position) => (position, transitions.inst(position), None),
+            None => (
+                0,
+                Inst::new(CursorControl::Nop, Oper::Halt),
+                Some(T::from_i32(0)),
+            ),
+        };
+        let storages = StorageSelector::new(env.channel.take());
+        let observer = env.observer.take();
+        let log = StepLog::new(observer.is_some());
+        let max_cells = env.history_cell_limit.unwrap_or(usize::MAX);
+        let history = env
+            .history
+            .map(|(interval, max_checkpoints)| History::new(interval, max_checkpoints, max_cells));
+        let loop_detector = if env.loop_detection {
+            Some(LoopDetector::new())
+        } else {
+            None
+        };
+        let blocks = match env.engine {
+            Engine::Interpreter => None,
+            Engine::Compiled => Some(Blocks::new(&transitions, env.arithmetic_mode)),
+        };
+        Machine {
+            env,
+            cursor,
+            position,
+            inst,
+            transitions,
+            storages,
+            halted,
+            steps: 0,
+            written: 0,
+            observer,
+            log,
+            history,
+            replaying: false,
+            loop_detector,
+            blocks,
+            dry_run: false,
+        }
+    }
+
+    pub fn save(&self) -> MachineState<T> {
+        MachineState {
+            cursor: self.cursor,
+            storages: self.storages.snapshot(),
+            halted: self.halted.clone(),
+            steps: self.steps,
+            read: self.env.read,
+            written: self.written,
+        }
+    }
+
+    /// Replaces the state with `state`. The history recorded so far is forgotten.
+    ///
+    /// Fails without changing anything if the cursor of `state` is out of the code or moves by
+    /// other than 1 or 2 cells, if `state` selects a stack that doesn't exist or doesn't have 28
+    /// stacks, or if `state` selects the channel and the machine has none.
+    pub fn restore(&mut self, state: &MachineState<T>) -> Result<(), RestoreError> {
+        if self.transitions.position_of(state.cursor).is_none() {
+            return Err(RestoreError::InvalidCursor(state.cursor));
+        }
+        if let Select::Stack(id) = state.storages.select {
+            if !state::is_stack_id(id) {
+                return Err(RestoreError::InvalidStack(id));
+            }
+        }
+        if state.storages.stacks.len() != 28 {
+            return Err(RestoreError::StackCount(state.storages.stacks.len()));
+        }
+        if self.storages.check(state.storages.select).is_err() {
+            return Err(RestoreError::NoChannel);
+        }
+        self.load(state);
+        if let Some(history) = self.history.as_mut() {
+            history.clear();
+            history.restart(state.clone());
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, state: &MachineState<T>) {
+        self.cursor = state.cursor;
+        self.position = self.transitions.position_of(state.cursor).unwrap();
+        self.inst = self.transitions.inst(self.position);
+        self.storages.restore(&state.storages);
+        self.halted = state.halted.clone();
+        self.steps = state.steps;
+        self.env.read = state.read;
+        self.written = state.written;
+        if let Some(detector) = self.loop_detector.as_mut() {
+            detector.reset();
+        }
+    }
+
+    /// The steps of the latest checkpoint at or before `steps`, where `rewind()` starts
+    /// re-executing from. `None` if there's no history that reaches back to it.
+    pub fn checkpoint_before(&self, steps: u64) -> Option<u64> {
+        Some(self.history.as_ref()?.checkpoint_before(steps)?.steps)
+    }
+
+    /// Undoes the last step. Returns `false` if there's no history that reaches back to it.
+    pub fn step_back(&mut self) -> bool {
+        self.steps > 0 && self.rewind(self.steps - 1)
+    }
+
+    /// Goes back to the state after executing `steps` instructions, re-executing them from the
+    /// closest checkpoint. Returns `false` if there's no history that reaches back to it.
+    pub fn rewind(&mut self, steps: u64) -> bool {
+        if steps > self.steps {
+            return false;
+        }
+        let state = match self
+            .history
+            .as_ref()
+            .and_then(|h| h.checkpoint_before(steps))
+        {
+            Some(state) => state.clone(),
+            None => return false,
+        };
+        self.load(&state);
+        while self.steps < steps {
+            if !matches!(self.step(), Ok(StepEvent::Executed { .. })) {
+                unreachable!("a recorded step has changed");
+            }
+        }
+        true
+    }
+
+    pub fn cursor(&self) -> Cursor {
+        self.cursor
+    }
+
+    /// The instruction under the cursor, which will be executed by the next `step()`.
+    pub fn inst(&self) -> Inst {
+        self.inst
+    }
+
+    pub fn storages(&self) -> &StorageSelector<'a, T> {
+        &self.storages
+    }
+
+    pub fn halted(&self) -> Option<T> {
+        self.halted.clone()
+    }
+
+    /// The number of instructions executed so far.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// The number of bytes read from the input so far.
+    pub fn read(&self) -> u64 {
+        self.env.read
+    }
+
+    /// The number of bytes written to the output so far.
+    pub fn written(&self) -> u64 {
+        self.written
+    }
+
+    /// Runs until halted, or until the fuel runs out, a limit is exceeded, or it gets
+    /// cancelled or timed out.
+    pub fn run(&mut self) -> Result<Outcome<T>, ExecError<T>> {
         loop {
-            let mut reverse = false;
-            match inst.oper {
-                Oper::Nop => {}
-                Oper::Halt => return storages.selected().try_pop().unwrap_or(0),
-                Oper::Add => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = b.wrapping_add(a);
-                    } else {
-                        reverse = true;
-                    }
+            if self.halted.is_none() {
+                if let Some(outcome) = self.check_stop() {
+                    return Ok(outcome);
                 }
-                Oper::Mul => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = b.wrapping_mul(a);
-                    } else {
-                        reverse = true;
+                if self.is_plain() {
+                    // Runs up to the next check of the fuel or the interrupts.
+                    let mut steps =
+                        INTERRUPT_CHECK_INTERVAL - self.steps % INTERRUPT_CHECK_INTERVAL;
+                    if let Some(fuel) = self.env.fuel {
+                        steps = steps.min(fuel - self.steps);
                     }
+                    self.run_plain(steps)?;
+                    continue;
                 }
-                Oper::Sub => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = b.wrapping_sub(a);
-                    } else {
-                        reverse = true;
+            }
+            match self.step()? {
+                StepEvent::Executed { .. } => {
+                    if let Some(outcome) = self.check_loop() {
+                        return Ok(outcome);
                     }
                 }
-                Oper::Div => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = b.wrapping_div(a);
-                    } else {
-                        reverse = true;
-                    }
+                StepEvent::Halted { value, .. } => return Ok(Outcome::Halted(value)),
+                StepEvent::LimitExceeded { limit, .. } => {
+                    return Ok(Outcome::LimitExceeded {
+                        limit,
+                        steps: self.steps,
+                        cursor: self.cursor,
+                        storages: self.storages.snapshot(),
+                    })
                 }
-                Oper::Mod => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = b.wrapping_rem(a);
-                    } else {
-                        reverse = true;
+            }
+        }
+    }
+
+    /// Checks what `run()` checks before each step, for running step by step with `step()`:
+    /// whether the fuel has run out and, every `INTERRUPT_CHECK_INTERVAL` steps, whether it's
+    /// cancelled or timed out. Returns the outcome `run()` would return.
+    pub fn check_stop(&self) -> Option<Outcome<T>> {
+        if self.env.fuel.is_some_and(|fuel| self.steps >= fuel) {
+            return Some(Outcome::FuelExhausted {
+                steps: self.steps,
+                cursor: self.cursor,
+                storages: self.storages.snapshot(),
+            });
+        }
+        if self.steps.is_multiple_of(INTERRUPT_CHECK_INTERVAL) {
+            return self.check_interrupt();
+        }
+        None
+    }
+
+    /// Whether `step()` has nothing to do but executing instructions: there's no observer,
+    /// history, limit, or loop detection.
+    fn is_plain(&self) -> bool {
+        self.observer.is_none()
+            && self.history.is_none()
+            && self.loop_detector.is_none()
+            && self.env.cell_limit.is_none()
+            && self.env.output_limit.is_none()
+    }
+
+    /// Executes up to `steps` instructions like `step()`, when `is_plain()`, or until halted.
+    /// The cursor is only updated from `position` at the end.
+    fn run_plain(&mut self, steps: u64) -> Result<(), ExecError<T>> {
+        let result = self.run_plain_inner(steps);
+        self.cursor = self.transitions.cursor(self.position);
+        result.map_err(|kind| ExecError::new(self.cursor.address, kind))
+    }
+
+    fn run_plain_inner(&mut self, steps: u64) -> Result<(), ExecErrorKind<T>> {
+        let end = self.steps + steps;
+        while self.steps < end {
+            if let Some(blocks) = self.blocks.as_mut() {
+                let block = blocks.get(&self.transitions, self.position);
+                if block.steps <= end - self.steps {
+                    if let Some(stack) = self.storages.selected_stack() {
+                        if block.run(stack) {
+                            self.steps += block.steps;
+                            self.position = block.end;
+                            self.inst = self.transitions.inst(self.position);
+                            continue;
+                        }
                     }
                 }
-                Oper::WriteChar => {
-                    if let Some(value) = storages.selected().try_pop() {
-                        write!(
-                            self.output,
-                            "{}",
-                            std::char::from_u32(value as u32).unwrap()
-                        )
-                        .unwrap();
-                    } else {
-                        reverse = true;
-                    }
+            }
+            let reverse = match self.execute_oper(self.inst.oper)? {
+                Some(reverse) => reverse,
+                None => {
+                    self.steps += 1;
+                    return Ok(());
                 }
-                Oper::WriteInt => {
-                    if let Some(value) = storages.selected().try_pop() {
-                        write!(self.output, "{}", value).unwrap();
-                    } else {
-                        reverse = true;
-                    }
+            };
+            self.steps += 1;
+            self.position = self.transitions.next(self.position, reverse);
+            self.inst = self.transitions.inst(self.position);
+        }
+        Ok(())
+    }
+
+    /// Executes the instruction under the cursor and moves the cursor to the next one.
+    /// Once halted, the machine stays halted and keeps returning the same event.
+    ///
+    /// On an error or an exceeded limit, the cursor stays on the failed instruction.
+    pub fn step(&mut self) -> Result<StepEvent<T>, ExecError<T>> {
+        let address = self.cursor.address;
+        if let Some(value) = self.halted.clone() {
+            return Ok(StepEvent::Halted { address, value });
+        }
+
+        let inst = self.inst;
+        if let Some(limit) = self.exceeded_limit(inst.oper).filter(|_| !self.dry_run) {
+            return Ok(StepEvent::LimitExceeded { address, limit });
+        }
+        let select = self.storages.current();
+        self.replaying = self.dry_run;
+        if let Some(history) = self.history.as_ref().filter(|_| !self.dry_run) {
+            self.replaying = history.is_replaying(self.steps);
+            if history.needs_checkpoint(self.steps) {
+                let state = self.save();
+                self.history.as_mut().unwrap().push_checkpoint(state);
+            }
+        }
+        let replaying = self.replaying;
+        if let Some(observer) = self.observer.as_mut().filter(|_| !replaying) {
+            self.log.clear();
+            observer.before_step(address, inst, select);
+        }
+        let reverse = match self.execute_oper(inst.oper) {
+            Ok(reverse) => reverse,
+            Err(kind) => return Err(ExecError::new(address, kind)),
+        };
+        self.steps += 1;
+        if let Some(observer) = self.observer.as_mut().filter(|_| !replaying) {
+            observer.after_step(&ObservedStep {
+                address,
+                direction: self.cursor.step,
+                inst,
+                reversed: reverse.unwrap_or(false),
+                select,
+                popped: &self.log.popped,
+                pushed: &self.log.pushed,
+            });
+        }
+        let reverse = match reverse {
+            Some(reverse) => reverse,
+            None => {
+                self.record_step(select, inst.oper);
+                let value = self.halted.clone().unwrap();
+                return Ok(StepEvent::Halted { address, value });
+            }
+        };
+
+        self.position = self.transitions.next(self.position, reverse);
+        self.cursor = self.transitions.cursor(self.position);
+        self.inst = self.transitions.inst(self.position);
+        self.record_step(select, inst.oper);
+        Ok(StepEvent::Executed {
+            address,
+            inst,
+            reversed: reverse,
+        })
+    }
+
+    /// Moves the frontier of the history after executing a step for the first time.
+    fn record_step(&mut self, select: Select, oper: Oper) {
+        if self.replaying {
+            return;
+        }
+        let touches_channel = match oper {
+            Oper::Nop | Oper::Select(_) => false,
+            Oper::Move(Select::Channel) => true,
+            _ => select == Select::Channel,
+        };
+        if let Some(detector) = self.loop_detector.as_mut() {
+            if touches_channel || matches!(oper, Oper::ReadChar | Oper::ReadInt) {
+                detector.reset();
+            }
+        }
+        if self.history.is_none() {
+            return;
+        }
+        let state = if touches_channel {
+            Some(self.save())
+        } else {
+            None
+        };
+        let history = self.history.as_mut().unwrap();
+        history.frontier = self.steps;
+        if let Some(state) = state {
+            history.restart(state);
+        }
+    }
+
+    /// Checks what `run()` checks after each executed step, for running step by step with
+    /// `step()`: whether the machine is in an infinite loop, if the loop detection is enabled.
+    /// Returns the outcome `run()` would return.
+    pub fn check_loop(&mut self) -> Option<Outcome<T>> {
+        let detector = self.loop_detector.as_mut()?;
+        let length = detector.check(self.cursor, &self.storages)?;
+
+        // Goes around the loop once more without effects to find where it goes.
+        let state = self.save();
+        let mut cycle = Vec::new();
+        self.dry_run = true;
+        for _ in 0..length {
+            match self.step() {
+                Ok(StepEvent::Executed { address, .. }) => cycle.push(address),
+                _ => unreachable!("a step in a loop has changed"),
+            }
+        }
+        self.dry_run = false;
+        self.load(&state);
+        Some(Outcome::InfiniteLoop {
+            steps: self.steps,
+            cursor: self.cursor,
+            storages: state.storages,
+            cycle,
+        })
+    }
+
+    fn check_interrupt(&self) -> Option<Outcome<T>> {
+        if self
+            .env
+            .cancel_handle
+            .as_ref()
+            .is_some_and(CancelHandle::is_cancelled)
+        {
+            return Some(Outcome::Cancelled {
+                steps: self.steps,
+                cursor: self.cursor,
+                storages: self.storages.snapshot(),
+            });
+        }
+        if self
+            .env
+            .deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return Some(Outcome::TimedOut {
+                steps: self.steps,
+                cursor: self.cursor,
+                storages: self.storages.snapshot(),
+            });
+        }
+        None
+    }
+
+    /// Checks whether executing `oper` could go over a limit, before anything is changed.
+    fn exceeded_limit(&mut self, oper: Oper) -> Option<Limit> {
+        if let Some(cell_limit) = self.env.cell_limit {
+            let current = self.storages.current();
+            let grows = match oper {
+                Oper::ReadChar | Oper::ReadInt | Oper::Push(_) | Oper::Dup => {
+                    current != Select::Channel
                 }
-                Oper::Pop => {
-                    if storages.selected().try_pop().is_none() {
-                        reverse = true;
-                    }
+                Oper::Move(select) => current == Select::Channel && select != Select::Channel,
+                _ => false,
+            };
+            if grows && self.storages.cell_count() >= cell_limit {
+                return Some(Limit::Cells);
+            }
+        }
+        if let Some(output_limit) = self.env.output_limit {
+            let len = match (oper, self.storages.peek()) {
+                (Oper::WriteChar, Some(value)) => value.to_char().map_or(0, char::len_utf8),
+                (Oper::WriteInt, Some(value)) => value.to_string().len(),
+                _ => 0,
+            };
+            if self.written + len as u64 > output_limit {
+                return Some(Limit::Output);
+            }
+        }
+        None
+    }
+
+    /// Returns whether the cursor should be reversed, or `None` if halted.
+    fn execute_oper(&mut self, oper: Oper) -> Result<Option<bool>, ExecErrorKind<T>> {
+        let storages = &mut self.storages;
+        let log = &mut self.log;
+        let mut reverse = false;
+        match oper {
+            Oper::Nop => {}
+            Oper::Halt => {
+                let value = storages.try_pop();
+                if let Some(value) = &value {
+                    log.pop(value);
                 }
-                Oper::ReadChar => {
-                    let value = self.read_char();
-                    storages.selected().push(value);
+                self.halted = Some(value.unwrap_or_else(|| T::from_i32(0)));
+                return Ok(None);
+            }
+            Oper::Add | Oper::Mul | Oper::Sub | Oper::Div | Oper::Mod => {
+                let mode = self.env.arithmetic_mode;
+                let div_zero_policy = self.env.div_zero_policy;
+                reverse = !binary_op(storages, log, |a, b| {
+                    arithmetic(mode, div_zero_policy, oper, b, a)
+                })?
+            }
+            Oper::WriteChar => {
+                if let Some(value) = storages.peek() {
+                    let ch = value
+                        .to_char()
+                        .ok_or_else(|| ExecErrorKind::InvalidCodePoint(value.clone()))?;
+                    storages.try_pop();
+                    log.pop(&value);
+                    let mut buf = [0u8; 4];
+                    let text = ch.encode_utf8(&mut buf);
+                    self.written += text.len() as u64;
+                    if !self.replaying {
+                        self.env.output.write_all(text.as_bytes())?;
+                        if let Some(observer) = self.observer.as_mut() {
+                            observer.on_write(oper, text);
+                        }
+                    }
+                } else {
+                    reverse = true;
                 }
-                Oper::ReadInt => {
-                    let value = self.read_int();
-                    storages.selected().push(value);
+            }
+            Oper::WriteInt => {
+                if let Some(value) = storages.try_pop() {
+                    log.pop(&value);
+                    let text = value.to_string();
+                    self.written += text.len() as u64;
+                    if !self.replaying {
+                        self.env.output.write_all(text.as_bytes())?;
+                        if let Some(observer) = self.observer.as_mut() {
+                            observer.on_write(oper, &text);
+                        }
+                    }
+                } else {
+                    reverse = true;
                 }
-                Oper::Push(v) => storages.selected().push(v as i32),
-                Oper::Dup => {
-                    reverse = !storages.selected().dup();
+            }
+            Oper::Pop => match storages.try_pop() {
+                Some(value) => log.pop(&value),
+                None => reverse = true,
+            },
+            Oper::ReadChar | Oper::ReadInt => {
+                let recorded = match &self.history {
+                    Some(history) if self.replaying => history.recorded_read(self.steps),
+                    _ => None,
+                };
+                let value = match recorded {
+                    Some((value, read)) => {
+                        self.env.read = read;
+                        value
+                    }
+                    None => {
+                        let value = if let Oper::ReadChar = oper {
+                            self.env.read_char()?
+                        } else {
+                            self.env.read_int()?
+                        };
+                        if let Some(history) = self.history.as_mut() {
+                            history.record_read(self.steps, value.clone(), self.env.read);
+                        }
+                        if let Some(observer) = self.observer.as_mut() {
+                            observer.on_read(oper, value.as_ref());
+                        }
+                        value
+                    }
+                };
+                let value = match (value, self.env.eof_policy) {
+                    (Some(value), _) => value,
+                    (None, EofPolicy::PushNegativeOne) => T::from_i32(-1),
+                    (None, EofPolicy::Reverse) => return Ok(Some(true)),
+                    (None, EofPolicy::Error) => return Err(ExecErrorKind::UnexpectedEof),
+                };
+                log.push(&value);
+                storages.push(value);
+            }
+            Oper::Push(v) => {
+                let value = T::from_i32(v as i32);
+                log.push(&value);
+                storages.push(value);
+            }
+            Oper::Dup => {
+                reverse = !storages.dup();
+                if !reverse {
+                    if let Some(value) = storages.peek() {
+                        log.push(&value);
+                    }
                 }
-                Oper::Swap => {
-                    reverse = !storages.selected().swap();
+            }
+            Oper::Swap => {
+                let pair = storages.peek_pair();
+                reverse = !storages.swap();
+                if let Some((a, b)) = pair {
+                    log.pop(&a);
+                    log.pop(&b);
+                    log.push(&a);
+                    log.push(&b);
                 }
-                Oper::Select(select) => storages.select(select),
-                Oper::Move(select) => {
-                    if let Some(value) = storages.selected().try_pop() {
-                        storages.get_storage(select).push(value);
-                    } else {
-                        reverse = true;
-                    }
+            }
+            Oper::Select(select) => storages.select(select)?,
+            Oper::Move(select) => {
+                // Fails before popping a value if there's nowhere to move it.
+                storages.check(select)?;
+                if let Some(value) = storages.try_pop() {
+                    log.pop(&value);
+                    log.push(&value);
+                    storages.push_to(select, value);
+                } else {
+                    reverse = true;
                 }
-                Oper::Compare => {
-                    if let Some((a, b)) = storages.selected().binary_op_assign() {
-                        *b = (a <= *b) as i32;
-                    } else {
-                        reverse = true;
-                    };
+            }
+            Oper::Compare => {
+                reverse = !binary_op(storages, log, |a, b| Ok(Some(T::from_i32((a <= b) as i32))))?
+            }
+            Oper::Cond => match storages.try_pop() {
+                Some(value) => {
+                    log.pop(&value);
+                    reverse = value.is_zero();
                 }
-                Oper::Cond => match storages.selected().try_pop() {
-                    Some(value) if value != 0 => {}
-                    _ => reverse = true,
-                },
+                None => reverse = true,
+            },
+        };
+        Ok(Some(reverse))
+    }
+}
+
+/// Calculates `lhs (oper) rhs`, or returns `None` if the cursor should be reversed instead.
+fn arithmetic<T: Cell>(
+    mode: ArithmeticMode,
+    div_zero_policy: DivZeroPolicy,
+    oper: Oper,
+    lhs: T,
+    rhs: T,
+) -> Result<Option<T>, ExecErrorKind<T>> {
+    if let Oper::Div | Oper::Mod = oper {
+        if rhs.is_zero() {
+            return match div_zero_policy {
+                DivZeroPolicy::Error => Err(ExecErrorKind::DivisionByZero),
+                DivZeroPolicy::Reverse => Ok(None),
+                DivZeroPolicy::PushZero => Ok(Some(T::from_i32(0))),
             };
+        }
+    }
+    let result = match (mode, oper) {
+        (ArithmeticMode::Wrap, Oper::Add) => Some(lhs.wrapping_add(&rhs)),
+        (ArithmeticMode::Wrap, Oper::Sub) => Some(lhs.wrapping_sub(&rhs)),
+        (ArithmeticMode::Wrap, Oper::Mul) => Some(lhs.wrapping_mul(&rhs)),
+        (ArithmeticMode::Wrap, Oper::Div) => Some(lhs.wrapping_div(&rhs)),
+        (ArithmeticMode::Wrap, Oper::Mod) => Some(lhs.wrapping_rem(&rhs)),
+        (ArithmeticMode::Saturate, Oper::Add) => Some(lhs.saturating_add(&rhs)),
+        (ArithmeticMode::Saturate, Oper::Sub) => Some(lhs.saturating_sub(&rhs)),
+        (ArithmeticMode::Saturate, Oper::Mul) => Some(lhs.saturating_mul(&rhs)),
+        (ArithmeticMode::Saturate, Oper::Div) => Some(lhs.saturating_div(&rhs)),
+        (ArithmeticMode::Saturate, Oper::Mod) => Some(lhs.wrapping_rem(&rhs)),
+        (ArithmeticMode::Trap, Oper::Add) => lhs.checked_add(&rhs),
+        (ArithmeticMode::Trap, Oper::Sub) => lhs.checked_sub(&rhs),
+        (ArithmeticMode::Trap, Oper::Mul) => lhs.checked_mul(&rhs),
+        (ArithmeticMode::Trap, Oper::Div) => lhs.checked_div(&rhs),
+        (ArithmeticMode::Trap, Oper::Mod) => lhs.checked_rem(&rhs),
+        _ => unreachable!(),
+    };
+    match result {
+        Some(value) => Ok(Some(value)),
+        None => Err(ExecErrorKind::Overflow { oper, lhs, rhs }),
+    }
+}
 
-            inst = cursor.advance(&self.code, inst.cursor_control, reverse);
+/// Replaces the two values on the selected storage with the result of `op(first, second)`,
+/// where `first` is the value that would be popped first.
+/// Returns `false` without touching the storage if there are less than two values
+/// or `op` returns `None`, and leaves the storage untouched if `op` fails.
+fn binary_op<T, F>(
+    storages: &mut StorageSelector<'_, T>,
+    log: &mut StepLog<T>,
+    op: F,
+) -> Result<bool, ExecErrorKind<T>>
+where
+    T: Cell,
+    F: FnOnce(T, T) -> Result<Option<T>, ExecErrorKind<T>>,
+{
+    let (a, b) = match storages.peek_pair() {
+        Some(pair) => pair,
+        None => return Ok(false),
+    };
+    log.pop(&a);
+    log.pop(&b);
+    let value = match op(a, b)? {
+        Some(value) => value,
+        None => {
+            log.clear();
+            return Ok(false);
         }
+    };
+    log.push(&value);
+    storages.replace_pair(value);
+    Ok(true)
+}
+
+impl<'a, T: Cell> Env<'a, T> {
+    /// Returns `None` at the end of the input.
+    fn read_char(&mut self) -> Result<Option<T>, ExecErrorKind<T>> {
+        read_char(self.input, &mut self.read)
     }
 
-    fn read_char(&mut self) -> i32 {
-        let mut buf = [0u8; 4];
-        for i in 0..4 {
-            self.input
-                .read_exact(&mut buf[i..i + 1])
-                .expect("Read Error");
-            match std::str::from_utf8(&buf[0..i + 1]) {
-                Ok(s) => return s.chars().nth(0).unwrap() as i32,
-                Err(e) => {
-                    if e.error_len().is_none() {
-                        continue;
-                    } else {
-                        panic!("an unexpected byte was encountered");
-                    }
+    /// Returns `None` at the end of the input.
+    fn read_int(&mut self) -> Result<Option<T>, ExecErrorKind<T>> {
+        read_int(self.input, &mut self.read)
+    }
+}
+
+/// Reads a UTF-8 character, counting the bytes in `read`. Returns `None` at the end of the input.
+fn read_char<T: Cell>(
+    input: &mut dyn BufRead,
+    read: &mut u64,
+) -> Result<Option<T>, ExecErrorKind<T>> {
+    let mut buf = [0u8; 4];
+    for i in 0..4 {
+        match input.read_exact(&mut buf[i..i + 1]) {
+            Ok(()) => *read += 1,
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                if i == 0 {
+                    return Ok(None);
+                } else {
+                    return Err(ExecErrorKind::InvalidUtf8);
                 }
-            };
+            }
+            Err(e) => return Err(e.into()),
         }
-        unreachable!();
+        match std::str::from_utf8(&buf[0..i + 1]) {
+            Ok(s) => return Ok(Some(T::from_i32(s.chars().next().unwrap() as i32))),
+            Err(e) => {
+                if e.error_len().is_none() {
+                    continue;
+                } else {
+                    return Err(ExecErrorKind::InvalidUtf8);
+                }
+            }
+        };
     }
+    unreachable!();
+}
 
-    fn read_int(&mut self) -> i32 {
-        let mut line = String::new();
-        self.input.read_line(&mut line).expect("Read Error");
-        line.trim().parse::<i32>().expect("Parse error")
+/// Reads an integer in a line, counting the bytes in `read`. Returns `None` at the end of the
+/// input.
+fn read_int<T: Cell>(
+    input: &mut dyn BufRead,
+    read: &mut u64,
+) -> Result<Option<T>, ExecErrorKind<T>> {
+    let mut line = String::new();
+    let len = input.read_line(&mut line)?;
+    if len == 0 {
+        return Ok(None);
     }
+    *read += len as u64;
+    let line = line.trim();
+    T::parse(line)
+        .map(Some)
+        .ok_or_else(|| ExecErrorKind::MalformedInt(line.to_string()))
 }
 
-struct StorageSelector {
+pub struct StorageSelector<'a, T: Cell = i32> {
     select: Select,
-    stacks: [Stack; 28],
-    queue: Queue,
+    stacks: [Stack<T>; 28],
+    queue: Queue<T>,
+    channel: Option<&'a mut dyn Channel<T>>,
 }
 
-impl StorageSelector {
-    fn new() -> Self {
+impl<'a, T: Cell> StorageSelector<'a, T> {
+    fn new(channel: Option<&'a mut dyn Channel<T>>) -> Self {
         Self {
             select: Select::Stack(0),
-            stacks: Default::default(),
+            stacks: std::array::from_fn(|_| Stack::default()),
             queue: Default::default(),
+            channel,
+        }
+    }
+
+    /// The storage that instructions currently operate on.
+    pub fn current(&self) -> Select {
+        self.select
+    }
+
+    /// Values of the stack, from the bottom to the top.
+    pub fn stack(&self, id: u8) -> &[T] {
+        assert!(id < 28);
+        &self.stacks[id as usize].stack
+    }
+
+    /// Values of the queue, from the front to the back.
+    pub fn queue(&self) -> &VecDeque<T> {
+        &self.queue.queue
+    }
+
+    /// The number of values stored in the stacks and the queue.
+    pub fn cell_count(&self) -> usize {
+        self.stacks.iter().map(|s| s.stack.len()).sum::<usize>() + self.queue.queue.len()
+    }
+
+    /// Copies the values of the stacks and the queue. The channel is not included.
+    pub fn snapshot(&self) -> StorageSnapshot<T> {
+        StorageSnapshot {
+            select: self.select,
+            stacks: self.stacks.iter().map(|s| s.stack.clone()).collect(),
+            queue: self.queue.queue.iter().cloned().collect(),
+        }
+    }
+
+    fn state_hash(&self) -> u64 {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.select.hash(&mut hasher);
+        for stack in &self.stacks {
+            stack.stack.hash(&mut hasher);
+        }
+        self.queue.queue.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether the selection and the lengths of the stacks and the queue are the same.
+    fn same_lengths(&self, snapshot: &StorageSnapshot<T>) -> bool {
+        self.select == snapshot.select
+            && self
+                .stacks
+                .iter()
+                .zip(&snapshot.stacks)
+                .all(|(stack, values)| stack.stack.len() == values.len())
+            && self.queue.queue.len() == snapshot.queue.len()
+    }
+
+    /// Whether the selection and the values of the stacks and the queue are the same.
+    fn matches(&self, snapshot: &StorageSnapshot<T>) -> bool {
+        self.select == snapshot.select
+            && self
+                .stacks
+                .iter()
+                .zip(&snapshot.stacks)
+                .all(|(stack, values)| stack.stack == *values)
+            && self.queue.queue.iter().eq(&snapshot.queue)
+    }
+
+    /// Replaces the values of the stacks and the queue, and the selection.
+    fn restore(&mut self, snapshot: &StorageSnapshot<T>) {
+        self.select = snapshot.select;
+        for (stack, values) in self.stacks.iter_mut().zip(&snapshot.stacks) {
+            stack.stack.clone_from(values);
         }
+        self.queue.queue = snapshot.queue.iter().cloned().collect();
     }
 
-    fn select(&mut self, select: Select) {
+    /// The values of the selected storage if it's a stack.
+    fn selected_stack(&mut self) -> Option<&mut Vec<T>> {
+        match self.select {
+            Select::Stack(id) => Some(&mut self.stacks[id as usize].stack),
+            _ => None,
+        }
+    }
+
+    fn select(&mut self, select: Select) -> Result<(), ExecErrorKind<T>> {
+        self.check(select)?;
         self.select = select;
+        Ok(())
     }
 
-    fn selected(&mut self) -> &mut dyn Storage {
-        self.get_storage(self.select)
+    /// Fails if `select` is the channel and there's no channel.
+    fn check(&self, select: Select) -> Result<(), ExecErrorKind<T>> {
+        match (select, &self.channel) {
+            (Select::Channel, None) => Err(ExecErrorKind::NoChannel),
+            _ => Ok(()),
+        }
     }
 
-    fn get_storage(&mut self, select: Select) -> &mut dyn Storage {
+    // The operations below work on the selected storage, dispatching on `select` directly.
+    // `select()` only accepts the channel if there is one.
+
+    fn push(&mut self, value: T) {
+        self.push_to(self.select, value)
+    }
+
+    /// Pushes to `select`, which should have been checked with `check()`.
+    fn push_to(&mut self, select: Select, value: T) {
         match select {
-            Select::Stack(id) => {
-                assert!(id < 28);
-                &mut self.stacks[id as usize]
+            Select::Stack(id) => self.stacks[id as usize].push(value),
+            Select::Queue => self.queue.push(value),
+            Select::Channel => self.channel.as_mut().unwrap().push(value),
+        }
+    }
+
+    fn try_pop(&mut self) -> Option<T> {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].try_pop(),
+            Select::Queue => self.queue.try_pop(),
+            Select::Channel => self.channel.as_mut().unwrap().try_pop(),
+        }
+    }
+
+    fn peek(&self) -> Option<T> {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].peek(),
+            Select::Queue => self.queue.peek(),
+            Select::Channel => self.channel.as_ref().unwrap().peek(),
+        }
+    }
+
+    /// The first and the second values that would be popped.
+    fn peek_pair(&self) -> Option<(T, T)> {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].peek_pair(),
+            Select::Queue => self.queue.peek_pair(),
+            Select::Channel => self.channel.as_ref().unwrap().peek_pair(),
+        }
+    }
+
+    fn swap(&mut self) -> bool {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].swap(),
+            Select::Queue => self.queue.swap(),
+            Select::Channel => self.channel.as_mut().unwrap().swap(),
+        }
+    }
+
+    fn dup(&mut self) -> bool {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].dup(),
+            Select::Queue => self.queue.dup(),
+            Select::Channel => self.channel.as_mut().unwrap().dup(),
+        }
+    }
+
+    /// Pops two values and pushes `value`, which is done in place on the stacks.
+    /// There should be at least two values.
+    fn replace_pair(&mut self, value: T) {
+        match self.select {
+            Select::Stack(id) => self.stacks[id as usize].replace_pair(value),
+            Select::Queue => self.queue.replace_pair(value),
+            Select::Channel => {
+                let channel = self.channel.as_mut().unwrap();
+                channel.try_pop();
+                channel.try_pop();
+                channel.push(value);
             }
-            Select::Queue => &mut self.queue,
-            Select::Channel => unreachable!(),
         }
     }
 }
 
-trait Storage {
-    fn push(&mut self, value: i32);
-    fn try_pop(&mut self) -> Option<i32>;
-    fn binary_op_assign(&mut self) -> Option<(i32, &mut i32)>;
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StorageSnapshot<T = i32> {
+    pub select: Select,
+    /// The 28 stacks, each from the bottom to the top.
+    pub stacks: Vec<Vec<T>>,
+    /// From the front to the back.
+    pub queue: Vec<T>,
+}
+
+/// The storage selected by `ㅎ` batchim, which is left to the implementations by the specification.
+///
+/// The methods have the same semantics as the stacks and the queue.
+pub trait Channel<T: Cell = i32> {
+    fn push(&mut self, value: T);
+    fn try_pop(&mut self) -> Option<T>;
+    fn peek(&self) -> Option<T>;
+    /// The first and the second values that would be popped.
+    fn peek_pair(&self) -> Option<(T, T)>;
+    /// Swaps the first and the second values that would be popped.
+    /// Returns `false` if there are less than two values.
     fn swap(&mut self) -> bool;
+    /// Duplicates the value that would be popped. Returns `false` if there is no value.
     fn dup(&mut self) -> bool;
 }
 
-#[derive(Clone, Default)]
-struct Stack {
-    stack: Vec<i32>,
+/// A channel that behaves like the queue.
+#[derive(Clone)]
+pub struct MemoryChannel<T = i32> {
+    queue: Queue<T>,
+}
+
+impl<T: Cell> MemoryChannel<T> {
+    pub fn new() -> MemoryChannel<T> {
+        MemoryChannel {
+            queue: Queue::default(),
+        }
+    }
+
+    /// Values in the channel, from the front to the back.
+    pub fn values(&self) -> &VecDeque<T> {
+        &self.queue.queue
+    }
+}
+
+impl<T: Cell> Default for MemoryChannel<T> {
+    fn default() -> MemoryChannel<T> {
+        MemoryChannel::new()
+    }
+}
+
+impl<T: Cell> Channel<T> for MemoryChannel<T> {
+    fn push(&mut self, value: T) {
+        self.queue.push(value)
+    }
+
+    fn try_pop(&mut self) -> Option<T> {
+        self.queue.try_pop()
+    }
+
+    fn peek(&self) -> Option<T> {
+        self.queue.peek()
+    }
+
+    fn peek_pair(&self) -> Option<(T, T)> {
+        self.queue.peek_pair()
+    }
+
+    fn swap(&mut self) -> bool {
+        self.queue.swap()
+    }
+
+    fn dup(&mut self) -> bool {
+        self.queue.dup()
+    }
+}
+
+#[derive(Clone)]
+struct Stack<T> {
+    stack: Vec<T>,
+}
+
+impl<T> Default for Stack<T> {
+    fn default() -> Self {
+        Stack { stack: Vec::new() }
+    }
 }
 
-impl Storage for Stack {
-    fn push(&mut self, value: i32) {
+impl<T: Cell> Stack<T> {
+    fn push(&mut self, value: T) {
         self.stack.push(value)
     }
 
-    fn try_pop(&mut self) -> Option<i32> {
+    fn try_pop(&mut self) -> Option<T> {
         self.stack.pop()
     }
 
-    fn binary_op_assign(&mut self) -> Option<(i32, &mut i32)> {
+    fn peek(&self) -> Option<T> {
+        self.stack.last().cloned()
+    }
+
+    fn peek_pair(&self) -> Option<(T, T)> {
         let len = self.stack.len();
         if len >= 2 {
-            let a = self.stack.pop().unwrap();
-            let b = &mut self.stack[len - 2];
-            Some((a, b))
+            Some((self.stack[len - 1].clone(), self.stack[len - 2].clone()))
         } else {
             None
         }
@@ -707,29 +1893,41 @@ impl Storage for Stack {
             false
         }
     }
+    fn replace_pair(&mut self, value: T) {
+        self.stack.pop();
+        *self.stack.last_mut().unwrap() = value;
+    }
+}
+
+#[derive(Clone)]
+struct Queue<T> {
+    queue: VecDeque<T>,
 }
 
-#[derive(Clone, Default)]
-struct Queue {
-    queue: VecDeque<i32>,
+impl<T> Default for Queue<T> {
+    fn default() -> Self {
+        Queue {
+            queue: VecDeque::new(),
+        }
+    }
 }
 
-impl Storage for Queue {
-    fn push(&mut self, value: i32) {
+impl<T: Cell> Queue<T> {
+    fn push(&mut self, value: T) {
         self.queue.push_back(value)
     }
 
-    fn try_pop(&mut self) -> Option<i32> {
+    fn try_pop(&mut self) -> Option<T> {
         self.queue.pop_front()
     }
 
-    fn binary_op_assign(&mut self) -> Option<(i32, &mut i32)> {
+    fn peek(&self) -> Option<T> {
+        self.queue.front().cloned()
+    }
+
+    fn peek_pair(&self) -> Option<(T, T)> {
         if self.queue.len() >= 2 {
-            let a = self.queue.pop_front().unwrap();
-            let b = self.queue.pop_front().unwrap();
-            self.queue.push_back(b);
-            let b = self.queue.back_mut().unwrap();
-            Some((a, b))
+            Some((self.queue[0].clone(), self.queue[1].clone()))
         } else {
             None
         }
@@ -752,4 +1950,9 @@ impl Storage for Queue {
             false
         }
     }
+    fn replace_pair(&mut self, value: T) {
+        self.queue.pop_front();
+        self.queue.pop_front();
+        self.queue.push_back(value);
+    }
 }
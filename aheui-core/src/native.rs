@@ -0,0 +1,40 @@
+//! What the code generated by `#[아희(컴파일=네이티브)]` calls at runtime. Errors panic with
+//! the same messages as `Env::execute()`, and the end of the input is read as -1.
+
+use crate::{Address, Cell, ExecError, ExecErrorKind};
+use std::io::{BufRead, Write};
+
+pub fn fail<T: Cell>(row: i32, col: i32, kind: ExecErrorKind<T>) -> ! {
+    panic!("{}", ExecError::new(Address { row, col }, kind))
+}
+
+pub fn read_char<T: Cell>(input: &mut dyn BufRead, row: i32, col: i32) -> T {
+    match crate::read_char(input, &mut 0) {
+        Ok(value) => value.unwrap_or_else(|| T::from_i32(-1)),
+        Err(kind) => fail(row, col, kind),
+    }
+}
+
+pub fn read_int<T: Cell>(input: &mut dyn BufRead, row: i32, col: i32) -> T {
+    match crate::read_int(input, &mut 0) {
+        Ok(value) => value.unwrap_or_else(|| T::from_i32(-1)),
+        Err(kind) => fail(row, col, kind),
+    }
+}
+
+pub fn write_char<T: Cell>(output: &mut dyn Write, value: T, row: i32, col: i32) {
+    let ch = match value.to_char() {
+        Some(ch) => ch,
+        None => fail(row, col, ExecErrorKind::InvalidCodePoint(value)),
+    };
+    let mut buf = [0u8; 4];
+    if let Err(e) = output.write_all(ch.encode_utf8(&mut buf).as_bytes()) {
+        fail::<T>(row, col, e.into());
+    }
+}
+
+pub fn write_int<T: Cell>(output: &mut dyn Write, value: T, row: i32, col: i32) {
+    if let Err(e) = output.write_all(value.to_string().as_bytes()) {
+        fail::<T>(row, col, e.into());
+    }
+}
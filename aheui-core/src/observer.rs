@@ -0,0 +1,69 @@
+use crate::{Address, Cell, Inst, Oper, Select, Step};
+
+/// Callbacks invoked by `Machine` while executing, attached by `Env::with_observer()`.
+///
+/// Every method does nothing by default, so implementations only override what they need.
+pub trait Observer<T: Cell = i32> {
+    /// Called before executing `inst` at `address`, with the storage selected at the moment.
+    fn before_step(&mut self, _address: Address, _inst: Inst, _select: Select) {}
+
+    /// Called after an instruction has been executed successfully, including `Oper::Halt`.
+    fn after_step(&mut self, _step: &ObservedStep<'_, T>) {}
+
+    /// Called when `Oper::ReadChar` or `Oper::ReadInt` reads a value, or `None` at the end of
+    /// the input.
+    fn on_read(&mut self, _oper: Oper, _value: Option<&T>) {}
+
+    /// Called when `Oper::WriteChar` or `Oper::WriteInt` writes `text` to the output.
+    fn on_write(&mut self, _oper: Oper, _text: &str) {}
+}
+
+#[derive(Clone, Debug)]
+pub struct ObservedStep<'s, T = i32> {
+    pub address: Address,
+    /// The direction the cursor was moving in when it reached the instruction.
+    pub direction: Step,
+    pub inst: Inst,
+    pub reversed: bool,
+    /// The storage selected when the instruction started.
+    pub select: Select,
+    /// Values taken out of the storages, in order.
+    pub popped: &'s [T],
+    /// Values put into the storages, in order.
+    pub pushed: &'s [T],
+}
+
+/// Values popped and pushed by the current step, only recorded when there's an observer.
+#[derive(Debug)]
+pub(crate) struct StepLog<T> {
+    enabled: bool,
+    pub(crate) popped: Vec<T>,
+    pub(crate) pushed: Vec<T>,
+}
+
+impl<T: Cell> StepLog<T> {
+    pub(crate) fn new(enabled: bool) -> Self {
+        StepLog {
+            enabled,
+            popped: Vec::new(),
+            pushed: Vec::new(),
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.popped.clear();
+        self.pushed.clear();
+    }
+
+    pub(crate) fn pop(&mut self, value: &T) {
+        if self.enabled {
+            self.popped.push(value.clone());
+        }
+    }
+
+    pub(crate) fn push(&mut self, value: &T) {
+        if self.enabled {
+            self.pushed.push(value.clone());
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::{Address, Cell, ObservedStep, Observer};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct CellProfile {
+    pub executions: u64,
+    /// How many times the cursor was reversed after executing the cell.
+    pub reversals: u64,
+}
+
+/// An observer that counts executions per cell and per kind of `Oper`.
+#[derive(Clone, Debug, Default)]
+pub struct Profiler {
+    cells: HashMap<Address, CellProfile>,
+    opers: BTreeMap<&'static str, u64>,
+    steps: u64,
+}
+
+/// 256-color palette indices from cold to hot.
+const HEAT_COLORS: [u8; 10] = [27, 33, 39, 45, 49, 118, 190, 220, 208, 196];
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    pub fn cell(&self, address: Address) -> CellProfile {
+        self.cells.get(&address).copied().unwrap_or_default()
+    }
+
+    /// Executed cells in no particular order.
+    pub fn cells(&self) -> impl Iterator<Item = (Address, CellProfile)> + '_ {
+        self.cells
+            .iter()
+            .map(|(&address, &profile)| (address, profile))
+    }
+
+    /// Execution counts per `Oper::name()`, from the most executed.
+    pub fn opers(&self) -> Vec<(&'static str, u64)> {
+        let mut opers: Vec<_> = self.opers.iter().map(|(&k, &v)| (k, v)).collect();
+        opers.sort_by_key(|&(_, count)| std::cmp::Reverse(count));
+        opers
+    }
+
+    /// Renders `source` as a grid with the execution count after each cell, followed by the
+    /// histogram of the instructions. With `color`, cells are colored by ANSI escape codes from
+    /// blue (cold) to red (hot) and never executed cells are dimmed.
+    pub fn render(&self, source: &str, color: bool) -> String {
+        let max = self.cells.values().map(|p| p.executions).max().unwrap_or(0);
+        let width = max.to_string().len();
+        let mut buf = String::new();
+        for (row, line) in source.lines().enumerate() {
+            for (col, ch) in line.chars().enumerate() {
+                let address = Address {
+                    row: row as i32,
+                    col: col as i32,
+                };
+                let executions = self.cell(address).executions;
+                let count = if executions == 0 {
+                    String::new()
+                } else {
+                    executions.to_string()
+                };
+                // Keeps the grid aligned assuming non-ASCII characters are full-width.
+                let pad = if ch.is_ascii() { " " } else { "" };
+                if color {
+                    let style = match heat_color(executions, max) {
+                        Some(color) => format!("\x1b[38;5;{}m", color),
+                        None => "\x1b[2m".to_string(),
+                    };
+                    write!(buf, "{}{}{}{:>width$}\x1b[0m ", style, ch, pad, count).unwrap();
+                } else {
+                    write!(buf, "{}{}{:>width$} ", ch, pad, count).unwrap();
+                }
+            }
+            buf.truncate(buf.trim_end_matches(' ').len());
+            buf.push('\n');
+        }
+        let reversals: u64 = self.cells.values().map(|p| p.reversals).sum();
+        writeln!(buf).unwrap();
+        writeln!(buf, "steps: {}", self.steps).unwrap();
+        writeln!(buf, "reversals: {}", reversals).unwrap();
+        for (name, count) in self.opers() {
+            writeln!(buf, "{:<10}{:>12}", name, count).unwrap();
+        }
+        buf
+    }
+}
+
+/// Picks a color on a logarithmic scale, or `None` if never executed.
+fn heat_color(executions: u64, max: u64) -> Option<u8> {
+    if executions == 0 {
+        return None;
+    }
+    let ratio = if max <= 1 {
+        1.0
+    } else {
+        (executions as f64).ln() / (max as f64).ln()
+    };
+    let index = (ratio * (HEAT_COLORS.len() - 1) as f64).round() as usize;
+    Some(HEAT_COLORS[index.min(HEAT_COLORS.len() - 1)])
+}
+
+impl<T: Cell> Observer<T> for Profiler {
+    fn after_step(&mut self, step: &ObservedStep<'_, T>) {
+        let cell = self.cells.entry(step.address).or_default();
+        cell.executions += 1;
+        if step.reversed {
+            cell.reversals += 1;
+        }
+        *self.opers.entry(step.inst.oper.name()).or_default() += 1;
+        self.steps += 1;
+    }
+}
@@ -0,0 +1,182 @@
+use crate::{Address, Cell, Cursor, ParseStateError, Select, Step, StorageSnapshot};
+use std::fmt;
+use std::str::FromStr;
+
+const HEADER: &str = "aheui-state 1";
+
+/// Everything of `Machine` that changes by executing, except for the channel.
+///
+/// It's written and parsed as text, one item per line:
+///
+/// ```text
+/// aheui-state 1
+/// cursor 0 3 column 1
+/// select stack 0
+/// halted none
+/// steps 5
+/// read 3
+/// written 2
+/// stack 0 8 6
+/// queue 1 2
+/// ```
+///
+/// Values of stacks are from the bottom to the top, and of the queue from the front to the back.
+/// Empty stacks and the empty queue are omitted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MachineState<T = i32> {
+    pub cursor: Cursor,
+    pub storages: StorageSnapshot<T>,
+    pub halted: Option<T>,
+    pub steps: u64,
+    /// Bytes read from the input. The input of a restored machine should start from here.
+    pub read: u64,
+    /// Bytes written to the output.
+    pub written: u64,
+}
+
+impl<T: Cell> fmt::Display for MachineState<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        let Address { row, col } = self.cursor.address;
+        let (axis, delta) = match self.cursor.step {
+            Step::Row(delta) => ("row", delta),
+            Step::Column(delta) => ("column", delta),
+        };
+        writeln!(f, "cursor {} {} {} {}", row, col, axis, delta)?;
+        match self.storages.select {
+            Select::Stack(id) => writeln!(f, "select stack {}", id)?,
+            Select::Queue => writeln!(f, "select queue")?,
+            Select::Channel => writeln!(f, "select channel")?,
+        }
+        match &self.halted {
+            Some(value) => writeln!(f, "halted {}", value)?,
+            None => writeln!(f, "halted none")?,
+        }
+        writeln!(f, "steps {}", self.steps)?;
+        writeln!(f, "read {}", self.read)?;
+        writeln!(f, "written {}", self.written)?;
+        for (id, stack) in self.storages.stacks.iter().enumerate() {
+            if !stack.is_empty() {
+                write!(f, "stack {}", id)?;
+                write_values(f, stack)?;
+            }
+        }
+        if !self.storages.queue.is_empty() {
+            write!(f, "queue")?;
+            write_values(f, &self.storages.queue)?;
+        }
+        Ok(())
+    }
+}
+
+fn write_values<T: Cell>(f: &mut fmt::Formatter, values: &[T]) -> fmt::Result {
+    for value in values {
+        write!(f, " {}", value)?;
+    }
+    writeln!(f)
+}
+
+impl<T: Cell> FromStr for MachineState<T> {
+    type Err = ParseStateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty());
+        match lines.next() {
+            Some((_, HEADER)) => {}
+            Some((line, _)) => return Err(ParseStateError::new(line, "unknown header")),
+            None => return Err(ParseStateError::new(0, "empty")),
+        }
+
+        let mut cursor = None;
+        let mut select = None;
+        let mut halted = None;
+        let mut steps = None;
+        let mut read = None;
+        let mut written = None;
+        let mut stacks = vec![Vec::new(); 28];
+        let mut queue = Vec::new();
+        for (line, text) in lines {
+            let error = |message: &str| ParseStateError::new(line, message);
+            let words: Vec<&str> = text.split_whitespace().collect();
+            match words.as_slice() {
+                ["cursor", row, col, axis, delta] => {
+                    let address = Address {
+                        row: parse(row).ok_or_else(|| error("invalid row"))?,
+                        col: parse(col).ok_or_else(|| error("invalid column"))?,
+                    };
+                    let delta = parse(delta)
+                        .filter(|delta: &i8| matches!(delta.abs(), 1 | 2))
+                        .ok_or_else(|| error("invalid step"))?;
+                    let step = match *axis {
+                        "row" => Step::Row(delta),
+                        "column" => Step::Column(delta),
+                        _ => return Err(error("invalid step")),
+                    };
+                    cursor = Some(Cursor { address, step });
+                }
+                ["select", "stack", id] => {
+                    let id = parse(id)
+                        .filter(|&id| is_stack_id(id))
+                        .ok_or_else(|| error("invalid stack"))?;
+                    select = Some(Select::Stack(id));
+                }
+                ["select", "queue"] => select = Some(Select::Queue),
+                ["select", "channel"] => select = Some(Select::Channel),
+                ["halted", "none"] => halted = Some(None),
+                ["halted", value] => {
+                    let value = T::parse(value).ok_or_else(|| error("invalid value"))?;
+                    halted = Some(Some(value));
+                }
+                ["steps", value] => {
+                    steps = Some(parse(value).ok_or_else(|| error("invalid steps"))?)
+                }
+                ["read", value] => read = Some(parse(value).ok_or_else(|| error("invalid bytes"))?),
+                ["written", value] => {
+                    written = Some(parse(value).ok_or_else(|| error("invalid bytes"))?)
+                }
+                ["stack", id, values @ ..] => {
+                    let id = parse::<u8>(id)
+                        .filter(|&id| is_stack_id(id))
+                        .ok_or_else(|| error("invalid stack"))?;
+                    stacks[id as usize] =
+                        parse_values(values).ok_or_else(|| error("invalid value"))?;
+                }
+                ["queue", values @ ..] => {
+                    queue = parse_values(values).ok_or_else(|| error("invalid value"))?;
+                }
+                _ => return Err(error("unknown item")),
+            }
+        }
+
+        let missing = |name: &str| ParseStateError::new(0, format!("missing {}", name));
+        Ok(MachineState {
+            cursor: cursor.ok_or_else(|| missing("cursor"))?,
+            storages: StorageSnapshot {
+                select: select.ok_or_else(|| missing("select"))?,
+                stacks,
+                queue,
+            },
+            halted: halted.ok_or_else(|| missing("halted"))?,
+            steps: steps.ok_or_else(|| missing("steps"))?,
+            read: read.ok_or_else(|| missing("read"))?,
+            written: written.ok_or_else(|| missing("written"))?,
+        })
+    }
+}
+
+fn parse<N: FromStr>(s: &str) -> Option<N> {
+    s.parse().ok()
+}
+
+fn parse_values<T: Cell>(values: &[&str]) -> Option<Vec<T>> {
+    values.iter().map(|value| T::parse(value)).collect()
+}
+
+/// Indices of `ㅇ` and `ㅎ` are the queue and the channel.
+pub(crate) fn is_stack_id(id: u8) -> bool {
+    id < 27 && id != 21
+}
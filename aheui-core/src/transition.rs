@@ -0,0 +1,101 @@
+use crate::{Address, BorrowedCode, Cursor, Inst, Step};
+
+/// Where the cursor goes next from every cell in every direction, so that moving the cursor is
+/// a lookup instead of searching for the next cell like `Cursor::advance()`.
+///
+/// The table works on positions, which number the cursors on the code: a cell numbered in the
+/// reading order as in `BorrowedCode::code`, times 8, plus the direction the cursor entered it.
+pub struct TransitionTable {
+    index: Vec<usize>,
+    cells: Vec<(Address, Inst)>,
+    /// The next positions after executing the cell of each position, without and with
+    /// reversing the cursor.
+    next: Vec<[u32; 2]>,
+}
+
+impl TransitionTable {
+    pub fn new(code: &BorrowedCode) -> TransitionTable {
+        let mut cells = Vec::with_capacity(code.code.len());
+        for (row, range) in code.index.windows(2).enumerate() {
+            for (col, &inst) in code.code[range[0]..range[1]].iter().enumerate() {
+                let address = Address {
+                    row: row as i32,
+                    col: col as i32,
+                };
+                cells.push((address, inst));
+            }
+        }
+        let mut table = TransitionTable {
+            index: code.index.to_vec(),
+            cells,
+            next: Vec::new(),
+        };
+        let next = (0..table.cells.len() * STEPS.len())
+            .map(|position| {
+                let cursor = table.cursor(position);
+                let inst = table.inst(position);
+                let mut next = [0; 2];
+                for (next, &reverse) in next.iter_mut().zip(&[false, true]) {
+                    let mut cursor = cursor;
+                    cursor.advance(code, inst.cursor_control, reverse);
+                    *next = table.position_of(cursor).unwrap() as u32;
+                }
+                next
+            })
+            .collect();
+        table.next = next;
+        table
+    }
+
+    /// The number of the positions, which are from 0.
+    pub fn positions(&self) -> usize {
+        self.next.len()
+    }
+
+    /// The position of `cursor`, or `None` if it's out of the code.
+    pub fn position_of(&self, cursor: Cursor) -> Option<usize> {
+        let Address { row, col } = cursor.address;
+        if row < 0 || col < 0 {
+            return None;
+        }
+        let (begin, end) = (
+            *self.index.get(row as usize)?,
+            *self.index.get(row as usize + 1)?,
+        );
+        let cell = begin + col as usize;
+        if cell >= end {
+            return None;
+        }
+        let direction = STEPS.iter().position(|&step| step == cursor.step)?;
+        Some(cell * STEPS.len() + direction)
+    }
+
+    pub fn cursor(&self, position: usize) -> Cursor {
+        Cursor {
+            address: self.cells[position / STEPS.len()].0,
+            step: STEPS[position % STEPS.len()],
+        }
+    }
+
+    /// The instruction under the cursor at `position`.
+    pub fn inst(&self, position: usize) -> Inst {
+        self.cells[position / STEPS.len()].1
+    }
+
+    /// Where the cursor at `position` goes after executing the instruction under it, like
+    /// `Cursor::advance()` with the cursor control of the instruction.
+    pub fn next(&self, position: usize, reverse: bool) -> usize {
+        self.next[position][reverse as usize] as usize
+    }
+}
+
+const STEPS: [Step; 8] = [
+    Step::Row(-2),
+    Step::Row(-1),
+    Step::Row(1),
+    Step::Row(2),
+    Step::Column(-2),
+    Step::Column(-1),
+    Step::Column(1),
+    Step::Column(2),
+];
@@ -0,0 +1,97 @@
+#![cfg(feature = "bigint")]
+
+use aheui_core::*;
+
+fn big(s: &str) -> BigInt {
+    BigInt::parse(s).unwrap()
+}
+
+/// Deterministic pseudo random values spanning a few digits, including the edge cases.
+fn samples() -> Vec<i128> {
+    let mut result = vec![0, 1, -1, 2, -2, i32::MAX as i128, i32::MIN as i128];
+    result.extend(&[u32::MAX as i128, 1 << 32, -(1 << 32), i64::MIN as i128]);
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    for _ in 0..40 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let shift = state % 60;
+        result.push((state >> shift) as i64 as i128);
+    }
+    result
+}
+
+#[test]
+fn test_parse_and_display() {
+    for value in samples() {
+        assert_eq!(big(&value.to_string()).to_string(), value.to_string());
+    }
+    assert_eq!(big("+42").to_string(), "42");
+    assert_eq!(big("-0").to_string(), "0");
+    assert_eq!(big("000123").to_string(), "123");
+    assert_eq!(format!("{:>5}", big("-12")), "  -12");
+    assert!(BigInt::parse("").is_none());
+    assert!(BigInt::parse("-").is_none());
+    assert!(BigInt::parse("12a").is_none());
+}
+
+#[test]
+fn test_arithmetic_matches_i128() {
+    let samples = samples();
+    for a in samples.iter() {
+        for b in samples.iter() {
+            let (x, y) = (big(&a.to_string()), big(&b.to_string()));
+            assert_eq!(x.wrapping_add(&y).to_string(), (a + b).to_string());
+            assert_eq!(x.wrapping_sub(&y).to_string(), (a - b).to_string());
+            assert_eq!(x.wrapping_mul(&y).to_string(), (a * b).to_string());
+            assert_eq!(x.cmp(&y), a.cmp(b));
+            if *b != 0 {
+                assert_eq!(x.wrapping_div(&y).to_string(), (a / b).to_string());
+                assert_eq!(x.wrapping_rem(&y).to_string(), (a % b).to_string());
+            }
+        }
+    }
+}
+
+#[test]
+fn test_long_division() {
+    let a = big("11790184577738583171520872861412518665678211592275841109096961");
+    let b = big("3433683820292512484657849089281");
+    assert_eq!(a.wrapping_div(&b), b);
+    assert!(a.wrapping_rem(&b).is_zero());
+    let c = a.wrapping_add(&big("12345"));
+    assert_eq!(c.wrapping_div(&b), b);
+    assert_eq!(c.wrapping_rem(&b), big("12345"));
+}
+
+#[test]
+fn test_to_i32_wrapping() {
+    assert_eq!(big("4294967338").to_i32_wrapping(), 42);
+    assert_eq!(big("-1").to_i32_wrapping(), -1);
+    assert_eq!(big("2147483648").to_i32_wrapping(), i32::MIN);
+}
+
+#[test]
+fn test_exec_bigint() {
+    let code = OwnedCode::parse(r"밞빠따빠따빠따빠따빠따빠따망희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let res = Env::<BigInt>::new_with_cell(&code, &mut input, &mut output).execute();
+    assert!(res.is_zero());
+    assert_eq!(
+        std::str::from_utf8(&output),
+        Ok("11790184577738583171520872861412518665678211592275841109096961")
+    );
+}
+
+#[test]
+fn test_exec_bigint_input() {
+    let code = OwnedCode::parse(r"방방따망희");
+    let mut input = std::io::Cursor::new("-123456789012345678901234567890\n10000000000\n");
+    let mut output = Vec::new();
+    Env::<BigInt>::new_with_cell(&code, &mut input, &mut output).execute();
+    assert_eq!(
+        std::str::from_utf8(&output),
+        Ok("-1234567890123456789012345678900000000000")
+    );
+}
@@ -0,0 +1,100 @@
+use aheui_core::*;
+
+const PROGRAMS: &[&str] = &[
+    // Hello, world!
+    r"밤밣따빠밣밟따뿌
+빠맣파빨받밤뚜뭏
+돋밬탕빠맣붏두붇
+볻뫃박발뚷투뭏붖
+뫃도뫃희멓뭏뭏붘
+뫃봌토범더벌뿌뚜
+뽑뽀멓멓더벓뻐뚠
+뽀덩벐멓뻐덕더벅",
+    // Fibonacci numbers.
+    r"반반나빠빠쌈다빠망빠쌈삼파싸사빠발발밖따따쟈하처우
+ㅇㅇㅇㅇㅇㅇ오어어어어어어어어어어어어어어어어어어",
+    // Counts down from 6^4 by 2.
+    r"붒ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇㅇㅇ
+붒ㅇㅇㅇ
+뚜ㅇ발희
+뚜아빠추
+ㅇㅇㅇ북
+아오ㅇ터",
+    // Underflows in the middle of straight-line runs.
+    r"빠파마밦다빠빠따다자맣밝받파마밦타망희",
+    r"바빠마마마바빠밣자마자망밦밦따따따따따따따따따따따따따망희",
+    // Stacks, the queue and moving values between them.
+    r"발받밤상따파빠싼반다쌍밝망망앙망희",
+];
+
+fn run(
+    source: &str,
+    engine: Engine,
+    mode: ArithmeticMode,
+    fuel: Option<u64>,
+) -> (Result<Outcome, (Address, String)>, String) {
+    let code = OwnedCode::parse(source);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut env = Env::new(&code, &mut input, &mut output)
+        .with_engine(engine)
+        .with_arithmetic_mode(mode);
+    if let Some(fuel) = fuel {
+        env = env.with_fuel(fuel);
+    }
+    let result = env
+        .try_execute()
+        .map_err(|e| (e.address, e.kind.to_string()));
+    (result, String::from_utf8(output).unwrap())
+}
+
+#[test]
+fn test_compiled_same_as_interpreter() {
+    for source in PROGRAMS {
+        for &mode in &[
+            ArithmeticMode::Wrap,
+            ArithmeticMode::Saturate,
+            ArithmeticMode::Trap,
+        ] {
+            let expected = run(source, Engine::Interpreter, mode, Some(1_000_000));
+            let actual = run(source, Engine::Compiled, mode, Some(1_000_000));
+            assert_eq!(actual, expected, "{:?} {:?}", source, mode);
+        }
+    }
+}
+
+#[test]
+fn test_compiled_fuel() {
+    // Runs out of fuel in the middle of the blocks.
+    for source in PROGRAMS {
+        for fuel in 0..200 {
+            let expected = run(
+                source,
+                Engine::Interpreter,
+                ArithmeticMode::Wrap,
+                Some(fuel),
+            );
+            let actual = run(source, Engine::Compiled, ArithmeticMode::Wrap, Some(fuel));
+            assert_eq!(actual, expected, "{:?} {}", source, fuel);
+        }
+    }
+}
+
+#[test]
+fn test_compiled_overflow() {
+    // 2^32 by squaring in a straight line.
+    let source = r"박빠따빠따빠따빠따빠따망희";
+    let (result, _) = run(source, Engine::Compiled, ArithmeticMode::Trap, None);
+    let err = result.unwrap_err();
+    assert_eq!(err.0, Address { row: 0, col: 10 });
+    assert_eq!(
+        run(source, Engine::Compiled, ArithmeticMode::Wrap, None).1,
+        "0"
+    );
+    assert_eq!(
+        run(source, Engine::Compiled, ArithmeticMode::Saturate, None).1,
+        i32::MAX.to_string()
+    );
+}
@@ -0,0 +1,72 @@
+use aheui_core::*;
+
+const BRANCH: &str = "방추\nㅇ희\nㅇ히";
+
+fn collect(input: &str) -> Coverage {
+    let code = OwnedCode::parse(BRANCH);
+    let mut input = std::io::Cursor::new(input.to_string());
+    let mut output = Vec::new();
+    let mut coverage = Coverage::new();
+    Env::new(&code, &mut input, &mut output)
+        .with_observer(&mut coverage)
+        .execute();
+    coverage
+}
+
+#[test]
+fn test_coverage_one_run() {
+    let code = OwnedCode::parse(BRANCH);
+    let coverage = collect("1\n");
+    assert_eq!(
+        coverage.hits(Address { row: 0, col: 1 }, Step::Column(1)),
+        BranchCount {
+            forward: 1,
+            reversed: 0
+        }
+    );
+    assert_eq!(
+        coverage.hits(Address { row: 0, col: 1 }, Step::Row(1)),
+        BranchCount::default()
+    );
+
+    let report = coverage.report(&code);
+    assert_eq!(report.cells, 4);
+    assert_eq!(report.covered, 3);
+    assert_eq!(report.uncovered, vec![Address { row: 2, col: 1 }]);
+    assert_eq!(
+        report.one_sided,
+        vec![(
+            Address { row: 0, col: 1 },
+            BranchCount {
+                forward: 1,
+                reversed: 0
+            }
+        )]
+    );
+    assert_eq!(
+        report.to_string(),
+        "covered 3 of 4 cells\n\
+         never executed: row 2, column 1\n\
+         one-sided condition: row 0, column 1, never reversed\n"
+    );
+}
+
+#[test]
+fn test_coverage_merge() {
+    let code = OwnedCode::parse(BRANCH);
+    let mut coverage = collect("1\n");
+    coverage.merge(&collect("0\n"));
+    coverage.merge(&collect("2\n"));
+    assert_eq!(
+        coverage.cell(Address { row: 0, col: 1 }),
+        BranchCount {
+            forward: 2,
+            reversed: 1
+        }
+    );
+
+    let report = coverage.report(&code);
+    assert_eq!(report.covered, 4);
+    assert!(report.uncovered.is_empty());
+    assert!(report.one_sided.is_empty());
+}
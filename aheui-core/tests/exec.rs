@@ -81,3 +81,825 @@ fn test_helloworld2() {
     assert_eq!(res, 0);
     assert_eq!(std::str::from_utf8(&output), Ok("안녕하세요?\n"));
 }
+
+#[test]
+fn test_machine_step() {
+    let code = OwnedCode::parse(r"마밦밠따희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+
+    match machine.step().unwrap() {
+        StepEvent::Executed {
+            address, reversed, ..
+        } => {
+            assert_eq!(address, Address { row: 0, col: 0 });
+            assert!(reversed);
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+    assert_eq!(machine.cursor().address, Address { row: 0, col: 4 });
+    match machine.step().unwrap() {
+        StepEvent::Halted { address, value } => {
+            assert_eq!(address, Address { row: 0, col: 4 });
+            assert_eq!(value, 0);
+        }
+        event => panic!("unexpected event: {:?}", event),
+    }
+    assert_eq!(machine.halted(), Some(0));
+}
+
+#[test]
+fn test_empty_code() {
+    // The first line is empty, so there's no instruction at the start.
+    for source in &["", "\n희"] {
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::Cursor::new("");
+        let mut output = Vec::new();
+        let outcome = Env::new(&code, &mut input, &mut output)
+            .with_engine(Engine::Compiled)
+            .try_execute()
+            .unwrap();
+        assert_eq!(outcome, Outcome::Halted(0));
+
+        let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+        assert_eq!(machine.halted(), Some(0));
+        assert!(matches!(
+            machine.step().unwrap(),
+            StepEvent::Halted { value: 0, .. }
+        ));
+        assert_eq!(machine.steps(), 0);
+        assert!(output.is_empty());
+    }
+}
+
+#[test]
+fn test_machine_storages() {
+    let code = OwnedCode::parse(r"밦밠따빠상밦희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+    for _ in 0..5 {
+        machine.step().unwrap();
+    }
+    assert_eq!(machine.storages().stack(0), &[42, 42]);
+    assert!(machine.storages().queue().is_empty());
+    assert_eq!(machine.run().unwrap(), Outcome::Halted(6));
+    assert_eq!(machine.storages().stack(0), &[42, 42]);
+}
+
+#[test]
+fn test_queue_arithmetic() {
+    // Pops 5 and 3 from the front and pushes 3 - 5 to the back.
+    let code = OwnedCode::parse(r"상발받밤타망망희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let res = Env::new(&code, &mut input, &mut output).execute();
+    assert_eq!(res, 0);
+    assert_eq!(std::str::from_utf8(&output), Ok("4-2"));
+}
+
+#[test]
+fn test_error_division_by_zero() {
+    for code in &[r"밠바나희", r"밠바라희"] {
+        let code = OwnedCode::parse(code);
+        let mut input = std::io::Cursor::new("");
+        let mut output = Vec::new();
+        let err = Env::new(&code, &mut input, &mut output)
+            .try_execute()
+            .unwrap_err();
+        assert_eq!(err.address, Address { row: 0, col: 2 });
+        assert!(matches!(err.kind, ExecErrorKind::DivisionByZero));
+    }
+}
+
+#[test]
+fn test_error_invalid_code_point() {
+    let code = OwnedCode::parse(r"바밠타맣희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .try_execute()
+        .unwrap_err();
+    assert_eq!(err.address, Address { row: 0, col: 3 });
+    assert!(matches!(err.kind, ExecErrorKind::InvalidCodePoint(-7)));
+}
+
+#[test]
+fn test_error_malformed_int() {
+    let code = OwnedCode::parse(r"방희");
+    let mut input = std::io::Cursor::new("abc\n");
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .try_execute()
+        .unwrap_err();
+    assert_eq!(err.address, Address { row: 0, col: 0 });
+    assert!(matches!(err.kind, ExecErrorKind::MalformedInt(ref line) if line == "abc"));
+}
+
+#[test]
+fn test_error_invalid_utf8() {
+    let code = OwnedCode::parse(r"밯희");
+    let mut input = std::io::Cursor::new(vec![0xffu8]);
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .try_execute()
+        .unwrap_err();
+    assert!(matches!(err.kind, ExecErrorKind::InvalidUtf8));
+}
+
+#[test]
+fn test_eof_push_negative_one() {
+    let code = OwnedCode::parse(r"밯망방망희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let res = Env::new(&code, &mut input, &mut output).execute();
+    assert_eq!(res, 0);
+    assert_eq!(std::str::from_utf8(&output), Ok("-1-1"));
+}
+
+#[test]
+fn test_eof_reverse() {
+    let code = OwnedCode::parse(
+        r"붛
+뫃
+희",
+    );
+    let mut input = std::io::Cursor::new("한글 abc");
+    let mut output = Vec::new();
+    let res = Env::new(&code, &mut input, &mut output)
+        .with_eof_policy(EofPolicy::Reverse)
+        .execute();
+    assert_eq!(res, 0);
+    assert_eq!(std::str::from_utf8(&output), Ok("한글 abc"));
+}
+
+#[test]
+fn test_eof_error() {
+    let code = OwnedCode::parse(
+        r"붛
+뫃
+희",
+    );
+    let mut input = std::io::Cursor::new("a");
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .with_eof_policy(EofPolicy::Error)
+        .try_execute()
+        .unwrap_err();
+    assert_eq!(err.address, Address { row: 0, col: 0 });
+    assert!(matches!(err.kind, ExecErrorKind::UnexpectedEof));
+    assert_eq!(std::str::from_utf8(&output), Ok("a"));
+}
+
+#[test]
+fn test_eof_in_the_middle_of_char() {
+    let code = OwnedCode::parse(r"밯희");
+    let mut input = std::io::Cursor::new(&"한".as_bytes()[..2]);
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .try_execute()
+        .unwrap_err();
+    assert!(matches!(err.kind, ExecErrorKind::InvalidUtf8));
+}
+
+#[test]
+fn test_memory_channel() {
+    let code = OwnedCode::parse(r"샇밦밠따빠쌓희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut channel = MemoryChannel::new();
+    let res = Env::new(&code, &mut input, &mut output)
+        .with_channel(&mut channel)
+        .execute();
+    assert_eq!(res, 42);
+    assert_eq!(channel.values(), &[42]);
+}
+
+#[derive(Default)]
+struct Counter {
+    next: i32,
+    received: Vec<i32>,
+}
+
+impl Channel for Counter {
+    fn push(&mut self, value: i32) {
+        self.received.push(value);
+    }
+
+    fn try_pop(&mut self) -> Option<i32> {
+        self.next += 1;
+        Some(self.next)
+    }
+
+    fn peek(&self) -> Option<i32> {
+        Some(self.next + 1)
+    }
+
+    fn peek_pair(&self) -> Option<(i32, i32)> {
+        Some((self.next + 1, self.next + 2))
+    }
+
+    fn swap(&mut self) -> bool {
+        false
+    }
+
+    fn dup(&mut self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn test_custom_channel() {
+    let code = OwnedCode::parse(r"밦쌓샇망망희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut channel = Counter::default();
+    let res = Env::new(&code, &mut input, &mut output)
+        .with_channel(&mut channel)
+        .execute();
+    assert_eq!(res, 3);
+    assert_eq!(std::str::from_utf8(&output), Ok("12"));
+    assert_eq!(channel.received, &[6]);
+}
+
+#[test]
+fn test_no_channel() {
+    for code in &[r"밦샇희", r"밦쌓희"] {
+        let code = OwnedCode::parse(code);
+        let mut input = std::io::Cursor::new("");
+        let mut output = Vec::new();
+        let err = Env::new(&code, &mut input, &mut output)
+            .try_execute()
+            .unwrap_err();
+        assert_eq!(err.address, Address { row: 0, col: 1 });
+        assert!(matches!(err.kind, ExecErrorKind::NoChannel));
+    }
+}
+
+const NINE_TO_THE_TENTH: &str = r"밞빠빠빠빠빠빠빠빠빠따따따따따따따따따망희";
+
+#[test]
+fn test_arithmetic_wrap() {
+    let code = OwnedCode::parse(NINE_TO_THE_TENTH);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    Env::new(&code, &mut input, &mut output).execute();
+    assert_eq!(std::str::from_utf8(&output), Ok("-808182895"));
+}
+
+#[test]
+fn test_arithmetic_saturate() {
+    let code = OwnedCode::parse(NINE_TO_THE_TENTH);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    Env::new(&code, &mut input, &mut output)
+        .with_arithmetic_mode(ArithmeticMode::Saturate)
+        .execute();
+    assert_eq!(std::str::from_utf8(&output), Ok("2147483647"));
+}
+
+#[test]
+fn test_arithmetic_trap() {
+    let code = OwnedCode::parse(NINE_TO_THE_TENTH);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .with_arithmetic_mode(ArithmeticMode::Trap)
+        .try_execute()
+        .unwrap_err();
+    assert_eq!(err.address, Address { row: 0, col: 18 });
+    match err.kind {
+        ExecErrorKind::Overflow {
+            oper: Oper::Mul,
+            lhs,
+            rhs,
+        } => assert_eq!((lhs, rhs), (9, 387420489)),
+        kind => panic!("unexpected error: {:?}", kind),
+    }
+
+    let mut output = Vec::new();
+    let res = Env::<i64>::new_with_cell(&code, &mut input, &mut output)
+        .with_arithmetic_mode(ArithmeticMode::Trap)
+        .execute();
+    assert_eq!(res, 0);
+    assert_eq!(std::str::from_utf8(&output), Ok("3486784401"));
+}
+
+const DIVIDE_BY_ZERO: &str = r"밠바누
+ㅇㅇ희
+ㅇㅇ몽";
+
+#[test]
+fn test_div_zero_error() {
+    let code = OwnedCode::parse(DIVIDE_BY_ZERO);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let err = Env::new(&code, &mut input, &mut output)
+        .with_div_zero_policy(DivZeroPolicy::Error)
+        .try_execute()
+        .unwrap_err();
+    assert_eq!(err.address, Address { row: 0, col: 2 });
+    assert!(matches!(err.kind, ExecErrorKind::DivisionByZero));
+}
+
+#[test]
+fn test_div_zero_reverse() {
+    for code in &[
+        DIVIDE_BY_ZERO.to_string(),
+        DIVIDE_BY_ZERO.replace('누', "루"),
+    ] {
+        let code = OwnedCode::parse(code);
+        let mut input = std::io::Cursor::new("");
+        let mut output = Vec::new();
+        let res = Env::new(&code, &mut input, &mut output)
+            .with_div_zero_policy(DivZeroPolicy::Reverse)
+            .execute();
+        assert_eq!(res, 7);
+        assert_eq!(std::str::from_utf8(&output), Ok("0"));
+    }
+}
+
+#[test]
+fn test_div_zero_push_zero() {
+    for code in &[
+        DIVIDE_BY_ZERO.to_string(),
+        DIVIDE_BY_ZERO.replace('누', "루"),
+    ] {
+        let code = OwnedCode::parse(code);
+        let mut input = std::io::Cursor::new("");
+        let mut output = Vec::new();
+        let res = Env::new(&code, &mut input, &mut output)
+            .with_div_zero_policy(DivZeroPolicy::PushZero)
+            .execute();
+        assert_eq!(res, 0);
+        assert_eq!(std::str::from_utf8(&output), Ok(""));
+    }
+}
+
+#[test]
+fn test_fuel_exhausted() {
+    let code = OwnedCode::parse("바");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_fuel(10)
+        .try_execute()
+        .unwrap();
+    match outcome {
+        Outcome::FuelExhausted {
+            steps,
+            cursor,
+            storages,
+        } => {
+            assert_eq!(steps, 10);
+            assert_eq!(cursor.address, Address { row: 0, col: 0 });
+            assert_eq!(storages.select, Select::Stack(0));
+            assert_eq!(storages.stacks[0], vec![0; 10]);
+            assert!(storages.queue.is_empty());
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[test]
+fn test_fuel_enough() {
+    let code = OwnedCode::parse("밣희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_fuel(2)
+        .try_execute()
+        .unwrap();
+    assert_eq!(outcome, Outcome::Halted(8));
+
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_fuel(1)
+        .try_execute()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::FuelExhausted { steps: 1, .. }));
+}
+
+#[test]
+fn test_cell_limit() {
+    let code = OwnedCode::parse("바");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_cell_limit(5)
+        .try_execute()
+        .unwrap();
+    match outcome {
+        Outcome::LimitExceeded {
+            limit,
+            steps,
+            storages,
+            ..
+        } => {
+            assert_eq!(limit, Limit::Cells);
+            assert_eq!(steps, 5);
+            assert_eq!(storages.stacks[0], vec![0; 5]);
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[test]
+fn test_output_limit() {
+    let code = OwnedCode::parse("받망");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_output_limit(4)
+        .try_execute()
+        .unwrap();
+    match outcome {
+        Outcome::LimitExceeded {
+            limit,
+            steps,
+            cursor,
+            storages,
+        } => {
+            assert_eq!(limit, Limit::Output);
+            assert_eq!(steps, 9);
+            assert_eq!(cursor.address, Address { row: 0, col: 1 });
+            assert_eq!(storages.stacks[0], vec![3]);
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+    assert_eq!(std::str::from_utf8(&output), Ok("3333"));
+}
+
+#[test]
+fn test_cancelled() {
+    let code = OwnedCode::parse("아");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let handle = CancelHandle::new();
+    handle.cancel();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_cancel_handle(handle)
+        .try_execute()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::Cancelled { steps: 0, .. }));
+
+    let handle = CancelHandle::new();
+    let canceller = {
+        let handle = handle.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            handle.cancel();
+        })
+    };
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_cancel_handle(handle)
+        .try_execute()
+        .unwrap();
+    canceller.join().unwrap();
+    match outcome {
+        Outcome::Cancelled { steps, cursor, .. } => {
+            assert!(steps > 0);
+            assert_eq!(cursor.address, Address { row: 0, col: 0 });
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[test]
+fn test_timed_out() {
+    let code = OwnedCode::parse("바");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let deadline = std::time::Instant::now() + std::time::Duration::from_millis(10);
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_deadline(deadline)
+        .try_execute()
+        .unwrap();
+    assert!(std::time::Instant::now() >= deadline);
+    match outcome {
+        Outcome::TimedOut {
+            steps, storages, ..
+        } => assert_eq!(storages.stacks[0].len() as u64, steps),
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[derive(Default)]
+struct Recorder {
+    before: Vec<(Address, Select)>,
+    after: Vec<(Address, Vec<i32>, Vec<i32>)>,
+    reads: Vec<Option<i32>>,
+    writes: String,
+}
+
+impl Observer for Recorder {
+    fn before_step(&mut self, address: Address, _inst: Inst, select: Select) {
+        self.before.push((address, select));
+    }
+
+    fn after_step(&mut self, step: &ObservedStep) {
+        self.after
+            .push((step.address, step.popped.to_vec(), step.pushed.to_vec()));
+    }
+
+    fn on_read(&mut self, _oper: Oper, value: Option<&i32>) {
+        self.reads.push(value.cloned());
+    }
+
+    fn on_write(&mut self, _oper: Oper, text: &str) {
+        self.writes.push_str(text);
+    }
+}
+
+#[test]
+fn test_observer() {
+    let code = OwnedCode::parse("밣밦다망방희");
+    let mut input = std::io::Cursor::new("42\n");
+    let mut output = Vec::new();
+    let mut recorder = Recorder::default();
+    let res = Env::new(&code, &mut input, &mut output)
+        .with_observer(&mut recorder)
+        .execute();
+    assert_eq!(res, 42);
+    assert_eq!(recorder.before.len(), 6);
+    assert!(recorder
+        .before
+        .iter()
+        .all(|&(_, select)| select == Select::Stack(0)));
+    let address = |col| Address { row: 0, col };
+    assert_eq!(
+        recorder.after,
+        vec![
+            (address(0), vec![], vec![8]),
+            (address(1), vec![], vec![6]),
+            (address(2), vec![6, 8], vec![14]),
+            (address(3), vec![14], vec![]),
+            (address(4), vec![], vec![42]),
+            (address(5), vec![42], vec![]),
+        ]
+    );
+    assert_eq!(recorder.reads, vec![Some(42)]);
+    assert_eq!(recorder.writes, "14");
+}
+
+#[test]
+fn test_step_back() {
+    let code = OwnedCode::parse("밣밦다망방희");
+    let mut input = std::io::Cursor::new("42\n");
+    let mut output = Vec::new();
+    {
+        let env = Env::new(&code, &mut input, &mut output).with_history(2, 10);
+        let mut machine = Machine::new(env);
+        assert_eq!(machine.run().unwrap(), Outcome::Halted(42));
+        assert_eq!(machine.steps(), 6);
+
+        assert!(machine.step_back());
+        assert_eq!(machine.steps(), 5);
+        assert_eq!(machine.halted(), None);
+        assert_eq!(machine.storages().stack(0), &[42]);
+
+        assert!(machine.rewind(3));
+        assert_eq!(machine.cursor().address, Address { row: 0, col: 3 });
+        assert_eq!(machine.storages().stack(0), &[14]);
+
+        // Doesn't write or read again.
+        assert_eq!(machine.run().unwrap(), Outcome::Halted(42));
+        assert!(machine.rewind(0));
+        assert!(!machine.step_back());
+    }
+    assert_eq!(std::str::from_utf8(&output), Ok("14"));
+}
+
+#[test]
+fn test_step_back_bounded() {
+    let code = OwnedCode::parse("밣밦다망방희");
+    let mut input = std::io::Cursor::new("42\n");
+    let mut output = Vec::new();
+    let env = Env::new(&code, &mut input, &mut output).with_history(1, 3);
+    let mut machine = Machine::new(env);
+    machine.run().unwrap();
+    assert!(!machine.rewind(2));
+    assert_eq!(machine.steps(), 6);
+    assert!(machine.rewind(3));
+    assert_eq!(machine.storages().stack(0), &[14]);
+}
+
+#[test]
+fn test_step_back_cell_limit() {
+    let code = OwnedCode::parse("반반반반희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let env = Env::new(&code, &mut input, &mut output)
+        .with_history(1, 100)
+        .with_history_cell_limit(5);
+    let mut machine = Machine::new(env);
+    assert_eq!(machine.run().unwrap(), Outcome::Halted(2));
+    // The latest checkpoint, before the step 4, holds 4 values, so no older one fits with it.
+    assert_eq!(machine.checkpoint_before(3), None);
+    assert!(!machine.rewind(3));
+    assert_eq!(machine.checkpoint_before(5), Some(4));
+    assert!(machine.rewind(4));
+    assert_eq!(machine.storages().stack(0), &[2, 2, 2, 2]);
+}
+
+#[test]
+fn test_step_back_channel() {
+    let code = OwnedCode::parse("밣쌓밦희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut channel = MemoryChannel::new();
+    {
+        let env = Env::new(&code, &mut input, &mut output)
+            .with_channel(&mut channel)
+            .with_history(1, 10);
+        let mut machine = Machine::new(env);
+        assert_eq!(machine.run().unwrap(), Outcome::Halted(6));
+        assert!(machine.step_back());
+        assert!(machine.step_back());
+        assert_eq!(machine.steps(), 2);
+        assert!(!machine.step_back());
+        assert_eq!(machine.run().unwrap(), Outcome::Halted(6));
+    }
+    assert_eq!(channel.values(), &[8]);
+}
+
+#[test]
+fn test_state_text() {
+    let code = OwnedCode::parse("밣밦다빠망방희");
+    let mut input = std::io::Cursor::new("42\n");
+    let mut output = Vec::new();
+    let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+    for _ in 0..5 {
+        machine.step().unwrap();
+    }
+    let state = machine.save();
+    let text = state.to_string();
+    assert_eq!(
+        text,
+        "aheui-state 1\n\
+         cursor 0 5 column 1\n\
+         select stack 0\n\
+         halted none\n\
+         steps 5\n\
+         read 0\n\
+         written 2\n\
+         stack 0 14\n"
+    );
+    assert_eq!(text.parse::<MachineState>(), Ok(state));
+
+    let state: MachineState = text
+        .replace("cursor 0 5", "cursor 0 4")
+        .replace("stack 0 14", "stack 0 30\nqueue 1 2")
+        .parse()
+        .unwrap();
+    let mut input = std::io::Cursor::new("12\n");
+    let mut output = Vec::new();
+    let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+    machine.restore(&state).unwrap();
+    assert_eq!(machine.storages().queue(), &[1, 2]);
+    assert_eq!(machine.run().unwrap(), Outcome::Halted(12));
+    assert_eq!(machine.steps(), 8);
+    assert_eq!(machine.read(), 3);
+    drop(machine);
+    assert_eq!(std::str::from_utf8(&output), Ok("30"));
+}
+
+#[test]
+fn test_restore_error() {
+    let code = OwnedCode::parse("밣밦다빠망방희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut machine = Machine::new(Env::new(&code, &mut input, &mut output));
+    machine.step().unwrap();
+    let state = machine.save();
+
+    let outside: MachineState = state
+        .to_string()
+        .replace("cursor 0 1", "cursor 5 5")
+        .parse()
+        .unwrap();
+    assert_eq!(
+        machine.restore(&outside),
+        Err(RestoreError::InvalidCursor(outside.cursor))
+    );
+    let channel: MachineState = state
+        .to_string()
+        .replace("select stack 0", "select channel")
+        .parse()
+        .unwrap();
+    assert_eq!(machine.restore(&channel), Err(RestoreError::NoChannel));
+
+    let mut stack = state.clone();
+    stack.storages.select = Select::Stack(40);
+    assert_eq!(machine.restore(&stack), Err(RestoreError::InvalidStack(40)));
+    stack.storages.select = Select::Stack(21);
+    assert_eq!(machine.restore(&stack), Err(RestoreError::InvalidStack(21)));
+    let mut partial = state.clone();
+    partial.storages.stacks.truncate(3);
+    assert_eq!(machine.restore(&partial), Err(RestoreError::StackCount(3)));
+    assert_eq!(machine.save(), state);
+}
+
+#[test]
+fn test_state_text_error() {
+    let err = "aheui-state 1\ncursor 0 0 row 1\nselect stack 21\n"
+        .parse::<MachineState>()
+        .unwrap_err();
+    assert_eq!(err, ParseStateError::new(3, "invalid stack"));
+    let err = "aheui-state 1\ncursor 0 0 row 1\n"
+        .parse::<MachineState>()
+        .unwrap_err();
+    assert_eq!(err.to_string(), "invalid machine state: missing select");
+    let err = "aheui-state 1\ncursor 0 0 row 3\n"
+        .parse::<MachineState>()
+        .unwrap_err();
+    assert_eq!(err, ParseStateError::new(2, "invalid step"));
+    let err = "aheui-state 2\n".parse::<MachineState>().unwrap_err();
+    assert_eq!(err, ParseStateError::new(1, "unknown header"));
+}
+
+#[test]
+fn test_infinite_loop() {
+    let code = OwnedCode::parse("바마\n희");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_loop_detection()
+        .try_execute()
+        .unwrap();
+    match outcome {
+        Outcome::InfiniteLoop {
+            cursor,
+            storages,
+            cycle,
+            ..
+        } => {
+            assert_eq!(cycle.len(), 2);
+            assert_eq!(cycle[0], cursor.address);
+            assert!(cycle.contains(&Address { row: 0, col: 0 }));
+            assert!(cycle.contains(&Address { row: 0, col: 1 }));
+            assert!(storages.stacks[0].len() <= 1);
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
+
+#[test]
+fn test_infinite_loop_not_detected() {
+    // The storage keeps growing.
+    let code = OwnedCode::parse("바");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_loop_detection()
+        .with_fuel(1000)
+        .try_execute()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::FuelExhausted { .. }));
+
+    // Reads the input on every round.
+    let code = OwnedCode::parse("방마");
+    let mut input = std::io::Cursor::new("1\n2\n");
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_loop_detection()
+        .with_fuel(1000)
+        .try_execute()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::FuelExhausted { .. }));
+}
+
+#[test]
+fn test_infinite_loop_growing_storage() {
+    // The cursor comes back to the saved one on every step, but the storage never repeats.
+    // Hashing the whole storage on each of these steps would take quadratic time.
+    let code = OwnedCode::parse("바");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let start = std::time::Instant::now();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_loop_detection()
+        .with_fuel(300_000)
+        .try_execute()
+        .unwrap();
+    assert!(matches!(outcome, Outcome::FuelExhausted { .. }));
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+}
+
+#[test]
+fn test_infinite_loop_with_output() {
+    let code = OwnedCode::parse("밣망");
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_loop_detection()
+        .try_execute()
+        .unwrap();
+    match outcome {
+        Outcome::InfiniteLoop { steps, cycle, .. } => {
+            assert_eq!(cycle.len(), 2);
+            assert_eq!(output.len() as u64, steps / 2);
+        }
+        outcome => panic!("unexpected outcome: {:?}", outcome),
+    }
+}
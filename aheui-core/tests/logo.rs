@@ -0,0 +1,54 @@
+//! Runs `logo` of the snippets within a time budget, so that the interpreter can't get much
+//! slower unnoticed. It only runs in a release build: `cargo test --release --test logo`.
+
+use aheui_core::*;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A generous bound, as `logo` itself hasn't been timed: a release build runs about 100 million
+/// steps per second, e.g. `count-down` of the `snippets` bench takes 2ms for 190000 steps.
+const BUDGET: Duration = Duration::from_secs(10);
+
+/// Finds `name` in `dir` and its subdirectories.
+fn find(dir: &Path, name: &str) -> Option<PathBuf> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+    paths.iter().find_map(|path| {
+        if path.is_dir() {
+            find(path, name)
+        } else if path.file_name().is_some_and(|file| file == name) {
+            Some(path.clone())
+        } else {
+            None
+        }
+    })
+}
+
+#[test]
+#[cfg_attr(debug_assertions, ignore)]
+fn test_logo_in_time() {
+    let snippets = Path::new(env!("CARGO_MANIFEST_DIR")).join("../snippets");
+    let path = find(&snippets, "logo.aheui").expect(
+        "logo.aheui is not found; check out the snippets with `git submodule update --init`",
+    );
+    let source = std::fs::read_to_string(&path).unwrap();
+    let expected = std::fs::read(path.with_extension("out")).unwrap();
+    let code = OwnedCode::parse(&source);
+    let mut input = &std::fs::read(path.with_extension("in")).unwrap_or_default()[..];
+    let mut output = Vec::new();
+    let started = Instant::now();
+    let outcome = Env::new(&code, &mut input, &mut output)
+        .with_deadline(started + BUDGET)
+        .try_execute()
+        .unwrap();
+    assert!(
+        matches!(outcome, Outcome::Halted(_)),
+        "logo has taken more than {:?}",
+        BUDGET
+    );
+    assert!(output == expected, "logo has printed a wrong output");
+}
@@ -0,0 +1,53 @@
+use aheui_core::*;
+
+fn profile(source: &str, fuel: u64) -> Profiler {
+    let code = OwnedCode::parse(source);
+    let mut input = std::io::Cursor::new("");
+    let mut output = Vec::new();
+    let mut profiler = Profiler::new();
+    Env::new(&code, &mut input, &mut output)
+        .with_observer(&mut profiler)
+        .with_fuel(fuel)
+        .try_execute()
+        .unwrap();
+    profiler
+}
+
+#[test]
+fn test_profiler_counts() {
+    let profiler = profile("아머\n바", 4);
+    assert_eq!(profiler.steps(), 4);
+    assert_eq!(
+        profiler.cell(Address { row: 0, col: 0 }),
+        CellProfile {
+            executions: 2,
+            reversals: 0
+        }
+    );
+    assert_eq!(
+        profiler.cell(Address { row: 0, col: 1 }),
+        CellProfile {
+            executions: 2,
+            reversals: 2
+        }
+    );
+    assert_eq!(
+        profiler.cell(Address { row: 1, col: 0 }),
+        CellProfile::default()
+    );
+    assert_eq!(profiler.opers(), vec![("Nop", 2), ("Pop", 2)]);
+}
+
+#[test]
+fn test_profiler_render() {
+    let profiler = profile("아머\n바 ", 4);
+    let expected = format!(
+        "아2 머2\n바\n\nsteps: 4\nreversals: 2\n{:<10}{:>12}\n{:<10}{:>12}\n",
+        "Nop", 2, "Pop", 2
+    );
+    assert_eq!(profiler.render("아머\n바 ", false), expected);
+
+    let colored = profiler.render("아머\n바 ", true);
+    assert!(colored.contains("\x1b[38;5;196m아2\x1b[0m"));
+    assert!(colored.contains("\x1b[2m바 \x1b[0m"));
+}
@@ -0,0 +1,51 @@
+use aheui_core::*;
+
+const STEPS: [Step; 8] = [
+    Step::Row(-2),
+    Step::Row(-1),
+    Step::Row(1),
+    Step::Row(2),
+    Step::Column(-2),
+    Step::Column(-1),
+    Step::Column(1),
+    Step::Column(2),
+];
+
+#[test]
+fn test_transition_table_matches_cursor() {
+    // Ragged lines and an empty line, so that vertical moves have to skip over missing cells,
+    // with every kind of cursor control.
+    let owned = OwnedCode::parse("아야어여오\n요\n\n우유으\n의이아아\n가");
+    let code = BorrowedCode::from(&owned);
+    let table = TransitionTable::new(&code);
+    for (row, range) in owned.index.windows(2).enumerate() {
+        for col in 0..range[1] - range[0] {
+            let address = Address {
+                row: row as i32,
+                col: col as i32,
+            };
+            let inst = code.get_inst(address).unwrap();
+            for &step in &STEPS {
+                let cursor = Cursor { address, step };
+                let position = table.position_of(cursor).unwrap();
+                assert_eq!(table.cursor(position), cursor);
+                for &reverse in &[false, true] {
+                    let mut expected = cursor;
+                    expected.advance(&code, inst.cursor_control, reverse);
+                    assert_eq!(table.cursor(table.next(position, reverse)), expected);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_transition_table_out_of_code() {
+    let owned = OwnedCode::parse("아아\n아");
+    let table = TransitionTable::new(&BorrowedCode::from(&owned));
+    let step = Step::Row(1);
+    for &(row, col) in &[(-1, 0), (0, -1), (0, 2), (1, 1), (2, 0)] {
+        let address = Address { row, col };
+        assert_eq!(table.position_of(Cursor { address, step }), None);
+    }
+}
@@ -30,6 +30,12 @@ mod kw {
     syn::custom_keyword!(명령행인자);
     syn::custom_keyword!(표준입력);
     syn::custom_keyword!(인자);
+
+    syn::custom_keyword!(컴파일);
+    syn::custom_keyword!(인터프리터);
+    syn::custom_keyword!(네이티브);
+
+    syn::custom_keyword!(커버리지);
 }
 
 #[derive(Debug)]
@@ -44,6 +50,16 @@ pub enum AttrItem {
         eq_token: Token![=],
         input: Input,
     },
+    Compile {
+        compile_token: kw::컴파일,
+        eq_token: Token![=],
+        compile: Compile,
+    },
+    Coverage {
+        coverage_token: kw::커버리지,
+        eq_token: Token![=],
+        name: Ident,
+    },
 }
 
 impl Parse for AttrItem {
@@ -67,6 +83,24 @@ impl Parse for AttrItem {
                 eq_token,
                 input,
             })
+        } else if lookahead.peek(kw::컴파일) {
+            let compile_token = input.parse()?;
+            let eq_token: Token![=] = input.parse()?;
+            let compile = input.parse()?;
+            Ok(AttrItem::Compile {
+                compile_token,
+                eq_token,
+                compile,
+            })
+        } else if lookahead.peek(kw::커버리지) {
+            let coverage_token = input.parse()?;
+            let eq_token: Token![=] = input.parse()?;
+            let name = input.parse()?;
+            Ok(AttrItem::Coverage {
+                coverage_token,
+                eq_token,
+                name,
+            })
         } else {
             Err(lookahead.error())
         }
@@ -94,6 +128,24 @@ impl ToTokens for AttrItem {
                 eq_token.to_tokens(tokens);
                 input.to_tokens(tokens);
             }
+            AttrItem::Compile {
+                compile_token,
+                eq_token,
+                compile,
+            } => {
+                compile_token.to_tokens(tokens);
+                eq_token.to_tokens(tokens);
+                compile.to_tokens(tokens);
+            }
+            AttrItem::Coverage {
+                coverage_token,
+                eq_token,
+                name,
+            } => {
+                coverage_token.to_tokens(tokens);
+                eq_token.to_tokens(tokens);
+                name.to_tokens(tokens);
+            }
         }
     }
 }
@@ -186,3 +238,31 @@ impl ToTokens for Input {
         }
     }
 }
+
+#[derive(Debug)]
+pub enum Compile {
+    Interpreter(kw::인터프리터),
+    Native(kw::네이티브),
+}
+
+impl Parse for Compile {
+    fn parse(input: &ParseBuffer) -> syn::parse::Result<Self> {
+        let lookahead = input.lookahead1();
+        if lookahead.peek(kw::인터프리터) {
+            Ok(Compile::Interpreter(input.parse()?))
+        } else if lookahead.peek(kw::네이티브) {
+            Ok(Compile::Native(input.parse()?))
+        } else {
+            Err(lookahead.error())
+        }
+    }
+}
+
+impl ToTokens for Compile {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        match self {
+            Compile::Interpreter(x) => x.to_tokens(tokens),
+            Compile::Native(x) => x.to_tokens(tokens),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::str::FromStr;
+
+use aheui_core::{Engine, Env, Outcome, OwnedCode};
+use proc_macro2::{Literal, TokenStream};
+use quote::quote;
+
+use crate::{native, Cell};
+
+/// 컴파일 중에 실행할 명령의 최대 개수
+const FUEL: u64 = 1_000_000;
+
+/// 입력을 읽지 않는 코드를 컴파일 중에 실행해, 그 출력을 `output`에 쓰고 종료 코드를 `result`에
+/// 담는 Rust 코드를 만듭니다.
+///
+/// 입력을 읽을 수 있거나, 정해진 명령 수 안에 끝나지 않거나, 오류가 생기면 `None`을 돌려줍니다.
+/// 이 경우 코드는 실행 시간에 실행되어야 합니다.
+pub fn evaluate(code: &OwnedCode, cell: Cell, cell_type: &TokenStream) -> Option<TokenStream> {
+    if !native::is_input_free(code) {
+        return None;
+    }
+    let (result, output) = match cell {
+        Cell::I32 => run::<i32>(code)?,
+        Cell::I64 => run::<i64>(code)?,
+        Cell::I128 => run::<i128>(code)?,
+    };
+    let result = TokenStream::from_str(&result).unwrap();
+    let write = if output.is_empty() {
+        quote! {}
+    } else {
+        let output = Literal::byte_string(&output);
+        quote! { output.write_all(#output).unwrap(); }
+    };
+    Some(quote! {
+        #write
+        let result: #cell_type = #result;
+    })
+}
+
+/// 종료 코드와 출력을 돌려줍니다.
+fn run<T: aheui_core::Cell>(code: &OwnedCode) -> Option<(String, Vec<u8>)> {
+    let mut input: &[u8] = &[];
+    let mut output = Vec::new();
+    let outcome = Env::<T>::new_with_cell(code, &mut input, &mut output)
+        .with_engine(Engine::Compiled)
+        .with_fuel(FUEL)
+        .try_execute();
+    match outcome {
+        Ok(Outcome::Halted(value)) => Some((value.to_string(), output)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate_i32(source: &str) -> Option<String> {
+        let code = OwnedCode::parse(source);
+        evaluate(&code, Cell::I32, &quote! { i32 }).map(|tokens| tokens.to_string())
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let expected = quote! { let result: i32 = 42; };
+        assert_eq!(evaluate_i32("밦밠따희"), Some(expected.to_string()));
+        let expected = quote! {
+            output.write_all(b"14").unwrap();
+            let result: i32 = -2;
+        };
+        assert_eq!(evaluate_i32("밣밦다망바반타희"), Some(expected.to_string()));
+    }
+
+    #[test]
+    fn test_evaluate_input() {
+        assert_eq!(evaluate_i32("밯망희"), None);
+        // Reads only after a branch.
+        assert_eq!(evaluate_i32("바추희\n　방망희"), None);
+    }
+
+    #[test]
+    fn test_evaluate_fuel() {
+        // Never halts.
+        assert_eq!(evaluate_i32("아"), None);
+        // Counts down from 6 * 9^5 by 2, which takes more steps than `FUEL`.
+        let source = "붒ㅇㅇㅇ\n붋ㅇㅇㅇ\n뚜ㅇㅇㅇ\n붋ㅇㅇㅇ\n뚜ㅇㅇㅇ\n붋ㅇㅇㅇ\n뚜ㅇㅇㅇ\n\
+                      붋ㅇㅇㅇ\n뚜ㅇㅇㅇ\n붋ㅇㅇ희\n뚜아빠추\nㅇㅇㅇ북\n아오ㅇ터";
+        assert_eq!(evaluate_i32(source), None);
+    }
+}
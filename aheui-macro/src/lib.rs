@@ -1,4 +1,4 @@
-#![feature(proc_macro_span, proc_macro_diagnostic)]
+#![feature(proc_macro_diagnostic)]
 extern crate proc_macro;
 
 use std::collections::HashMap;
@@ -9,11 +9,12 @@ use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use std::ops::Range;
 use std::str::FromStr;
-use syn;
 use syn::spanned::Spanned;
 use syn::*;
 
 mod attr;
+mod eval;
+mod native;
 
 #[proc_macro_attribute]
 pub fn 아희(
@@ -25,31 +26,89 @@ pub fn 아희(
 
     let config = parse_config(&attr, &item_fn.sig);
 
-    let code = {
+    let owned = {
         let lines = get_lines(&config, &item_fn);
-        let owned = OwnedCode::parse_lines(lines.iter().map(|x| x.as_str()));
-        let borrowed = owned.render_as_borrowed("::aheui_core::");
-        TokenStream::from_str(&borrowed).unwrap()
+        OwnedCode::parse_lines(lines.iter().map(|x| x.as_str()))
     };
     let fnsig = item_fn.sig;
     let input_prepare = config.input.prepare_input();
     let output_prepare = config.output.prepare_output();
     let output_convert = config.output.convert_output();
+    let cell = config.output.cell();
+
+    let execute = match (config.compile, &config.coverage) {
+        // 커버리지는 실행할 때마다 모아야 하므로 미리 실행하지 않습니다.
+        (Compile::Interpreter, Some(_)) => None,
+        (Compile::Interpreter, None) => eval::evaluate(&owned, config.output.cell_kind(), &cell),
+        (Compile::Native, Some(name)) => {
+            name.span()
+                .unwrap()
+                .error("커버리지는 인터프리터로 실행할 때만 모을 수 있습니다")
+                .emit();
+            panic!();
+        }
+        (Compile::Native, None) => native::generate(&owned, &cell),
+    };
+    let result = match execute {
+        Some(execute) => quote! {
+            #[allow(unused_imports, unused_mut, unused_variables)]
+            #fnsig {
+                use ::aheui_core;
+                use ::std::convert::TryInto;
+                use ::std::io::BufRead;
+                use ::std::io::Write;
+
+                #input_prepare
+                #output_prepare
+
+                #execute
+
+                #output_convert
+            }
+        },
+        None => {
+            let borrowed = owned.render_as_borrowed("::aheui_core::");
+            let code = TokenStream::from_str(&borrowed).unwrap();
+            let (coverage, execute) = match &config.coverage {
+                Some(name) => (
+                    quote! {
+                        static #name: ::aheui_core::SharedCoverage =
+                            ::aheui_core::SharedCoverage::new(#code);
+                    },
+                    quote! {
+                        let mut coverage = ::aheui_core::Coverage::new();
+                        let result = ::aheui_core::Env::<#cell>::new_with_cell(#name.code(), &mut input, &mut output)
+                            .with_observer(&mut coverage)
+                            .execute();
+                        #name.merge(&coverage);
+                    },
+                ),
+                None => (
+                    quote! {},
+                    quote! {
+                        let code = #code;
+                        let result = ::aheui_core::Env::<#cell>::new_with_cell(code, &mut input, &mut output)
+                            .execute();
+                    },
+                ),
+            };
+            quote! {
+                #coverage
 
-    let result = quote! {
-        #fnsig {
-            use ::aheui_core;
-            use ::std::convert::TryInto;
-            use ::std::io::BufRead;
-            use ::std::io::Write;
+                #fnsig {
+                    use ::aheui_core;
+                    use ::std::convert::TryInto;
+                    use ::std::io::BufRead;
+                    use ::std::io::Write;
 
-            #input_prepare
-            #output_prepare
+                    #input_prepare
+                    #output_prepare
 
-            let code = #code;
-            let result = ::aheui_core::Env::new(code, &mut input, &mut output).execute();
+                    #execute
 
-            #output_convert
+                    #output_convert
+                }
+            }
         }
     };
     proc_macro::TokenStream::from(result)
@@ -59,6 +118,9 @@ struct Config {
     quote: Quote,
     input: Input,
     output: Output,
+    compile: Compile,
+    /// 실행할 때마다 커버리지를 모아 둘 `static`의 이름
+    coverage: Option<Ident>,
 }
 
 #[derive(Debug)]
@@ -68,7 +130,7 @@ enum Quote {
     String,
 }
 
-impl<'a> From<&attr::Quote> for Quote {
+impl From<&attr::Quote> for Quote {
     fn from(quote: &attr::Quote) -> Quote {
         match quote {
             attr::Quote::Raw(_) => Quote::Raw,
@@ -78,6 +140,24 @@ impl<'a> From<&attr::Quote> for Quote {
     }
 }
 
+/// 코드를 실행하는 방식
+#[derive(Debug)]
+enum Compile {
+    /// `aheui_core`의 인터프리터로 실행합니다. 입력을 읽지 않는 코드는 컴파일 중에 미리 실행합니다.
+    Interpreter,
+    /// 코드를 Rust 코드로 옮겨 함께 컴파일합니다.
+    Native,
+}
+
+impl From<&attr::Compile> for Compile {
+    fn from(compile: &attr::Compile) -> Compile {
+        match compile {
+            attr::Compile::Interpreter(_) => Compile::Interpreter,
+            attr::Compile::Native(_) => Compile::Native,
+        }
+    }
+}
+
 #[derive(Debug)]
 enum Input {
     Stdin,
@@ -130,16 +210,50 @@ impl Input {
 enum Output {
     Stdout,
     String,
-    Code,
-    CodeString,
-    StringCode,
+    Code(Cell),
+    CodeString(Cell),
+    StringCode(Cell),
     ExitCode,
 }
 
+/// 저장공간의 정수 타입
+#[derive(Debug, Copy, Clone)]
+enum Cell {
+    I32,
+    I64,
+    I128,
+}
+
+impl Cell {
+    /// 반환 타입의 모든 값을 담을 수 있는 가장 작은 타입
+    fn for_integer(ident: &str) -> Cell {
+        match ident {
+            "i8" | "i16" | "i32" | "u8" | "u16" => Cell::I32,
+            "u32" | "i64" | "isize" => Cell::I64,
+            _ => Cell::I128,
+        }
+    }
+}
+
 impl Output {
+    fn cell_kind(&self) -> Cell {
+        match self {
+            Output::Code(cell) | Output::CodeString(cell) | Output::StringCode(cell) => *cell,
+            _ => Cell::I32,
+        }
+    }
+
+    fn cell(&self) -> TokenStream {
+        match self.cell_kind() {
+            Cell::I32 => quote! { i32 },
+            Cell::I64 => quote! { i64 },
+            Cell::I128 => quote! { i128 },
+        }
+    }
+
     fn prepare_output(&self) -> TokenStream {
         match self {
-            Output::Stdout | Output::Code | Output::ExitCode => quote! {
+            Output::Stdout | Output::Code(_) | Output::ExitCode => quote! {
                 let stdout = ::std::io::stdout();
                 let mut output = stdout.lock();
             },
@@ -157,15 +271,15 @@ impl Output {
             Output::String => quote! {
                 String::from_utf8(output).unwrap()
             },
-            Output::Code => quote! {
+            Output::Code(_) => quote! {
                 result.try_into().unwrap()
             },
-            Output::CodeString => quote! {
+            Output::CodeString(_) => quote! {
                 let exit_code = result.try_into().unwrap();
                 let string = String::from_utf8(output).unwrap();
                 (exit_code, string)
             },
-            Output::StringCode => quote! {
+            Output::StringCode(_) => quote! {
                 let exit_code = result.try_into().unwrap();
                 let string = String::from_utf8(output).unwrap();
                 (string, exit_code)
@@ -193,9 +307,26 @@ fn parse_config(attr: &attr::Attr, signature: &Signature) -> Config {
                 existing.span().unwrap().note("이전 옵션은 여기에").emit();
             }
 
-            match arg_item {
-                attr::AttrItem::Quote { quote, .. } => result = Quote::from(quote.clone()),
-                _ => {}
+            if let attr::AttrItem::Quote { quote, .. } = arg_item {
+                result = Quote::from(quote);
+            }
+        }
+        result
+    }
+    fn get_compile(attr: &attr::Attr) -> Compile {
+        let mut result = Compile::Interpreter;
+        for arg_item in attr.items.iter() {
+            if let attr::AttrItem::Compile { compile, .. } = arg_item {
+                result = Compile::from(compile);
+            }
+        }
+        result
+    }
+    fn get_coverage(attr: &attr::Attr) -> Option<Ident> {
+        let mut result = None;
+        for arg_item in attr.items.iter() {
+            if let attr::AttrItem::Coverage { name, .. } = arg_item {
+                result = Some(name.clone());
             }
         }
         result
@@ -205,6 +336,8 @@ fn parse_config(attr: &attr::Attr, signature: &Signature) -> Config {
         quote: get_quote(attr),
         input,
         output,
+        compile: get_compile(attr),
+        coverage: get_coverage(attr),
     }
 }
 
@@ -252,7 +385,7 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
         }
     }
 
-    fn is_integer(typ: &Type) -> bool {
+    fn get_integer_cell(typ: &Type) -> Option<Cell> {
         const INTEGER_TYPE_IDENTS: &[&str] = &[
             "isize", "i8", "i16", "i32", "i64", "i128", "u8", "usize", "u8", "u16", "u32", "u64",
             "u128",
@@ -261,14 +394,14 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
             Type::Path(path) => {
                 for ident in INTEGER_TYPE_IDENTS {
                     if path.path.is_ident(ident) {
-                        return true;
+                        return Some(Cell::for_integer(ident));
                     }
                 }
-                false
+                None
             }
-            Type::Group(group) if is_integer(&group.elem) => true,
-            Type::Paren(paren) if is_integer(&paren.elem) => true,
-            _ => false,
+            Type::Group(group) => get_integer_cell(&group.elem),
+            Type::Paren(paren) => get_integer_cell(&paren.elem),
+            _ => None,
         }
     }
 
@@ -277,10 +410,10 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
             ReturnType::Default => return Output::Stdout,
             ReturnType::Type(_, typ) => typ.as_ref(),
         };
-        let elems = match typ {
-            Type::Tuple(tuple) => tuple.elems.iter().collect::<Vec<_>>(),
+        let elems = match (typ, get_integer_cell(typ)) {
+            (Type::Tuple(tuple), _) => tuple.elems.iter().collect::<Vec<_>>(),
             _ if is_string(typ) => return Output::String,
-            _ if is_integer(typ) => return Output::Code,
+            (_, Some(cell)) => return Output::Code(cell),
             _ => {
                 typ.span()
                     .unwrap()
@@ -289,10 +422,19 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
                 panic!();
             }
         };
-        match elems.as_slice() {
-            &[] => Output::Stdout,
-            &[left, right] if is_string(left) && is_integer(right) => Output::StringCode,
-            &[left, right] if is_integer(left) && is_string(right) => Output::CodeString,
+        match *elems.as_slice() {
+            [] => Output::Stdout,
+            [left, right] => match (get_integer_cell(left), get_integer_cell(right)) {
+                (None, Some(cell)) if is_string(left) => Output::StringCode(cell),
+                (Some(cell), None) if is_string(right) => Output::CodeString(cell),
+                _ => {
+                    typ.span()
+                        .unwrap()
+                        .error("지원되지 않는 리턴 타입 형식")
+                        .emit();
+                    panic!()
+                }
+            },
             _ => {
                 typ.span()
                     .unwrap()
@@ -327,7 +469,6 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
             Some((Input::Auto, attr_item)) => {
                 if let Some(item) = attr_item {
                     item.span().unwrap().error("지원되지 않는 입력 형식").emit();
-                } else {
                 }
                 panic!();
             }
@@ -337,7 +478,7 @@ fn get_input_output(attr: &attr::Attr, signature: &Signature) -> (Input, Output)
     }
 }
 
-fn get_lines<'a>(config: &Config, item_fn: &'a ItemFn) -> Vec<String> {
+fn get_lines(config: &Config, item_fn: &ItemFn) -> Vec<String> {
     fn from_raw(item_fn: &ItemFn) -> Vec<String> {
         let range = parse_span(&item_fn.block.brace_token.span);
         let source_text = item_fn.block.span().unwrap().source_text().unwrap();
@@ -419,7 +560,7 @@ fn dedent(lines: Vec<String>) -> Vec<String> {
         loop {
             let char_a = chars_a.next();
             let char_b = chars_b.next();
-            if char_a != char_b || char_a == None && char_b == None {
+            if char_a != char_b || char_a.is_none() && char_b.is_none() {
                 return &a[0..(a.len() - chars_a.as_str().len())];
             }
         }
@@ -431,7 +572,7 @@ fn dedent(lines: Vec<String>) -> Vec<String> {
 
     let mut common_indent = None;
     for line in lines.iter() {
-        let indent = get_indent(&line);
+        let indent = get_indent(line);
         common_indent = Some(get_common_indent(indent, common_indent));
     }
     let indent_size = common_indent.unwrap().len();
@@ -445,11 +586,11 @@ fn dedent(lines: Vec<String>) -> Vec<String> {
 fn parse_span(span: &Span) -> Range<usize> {
     let mut buffer = Vec::new();
     write!(&mut buffer, "{:?}", span).unwrap();
-    let start_left = buffer.iter().position(|x| *x == '(' as u8).unwrap() + 1;
+    let start_left = buffer.iter().position(|x| *x == b'(').unwrap() + 1;
     let start_right = start_left
         + buffer[start_left..]
             .iter()
-            .position(|x| *x == '.' as u8)
+            .position(|x| *x == b'.')
             .unwrap();
     let end_left = start_right + 2;
     let end_right = buffer.len() - 1;
@@ -481,5 +622,5 @@ fn trim_empty_lines<'a, I: Iterator<Item = &'a str>>(lines: I) -> Vec<String> {
             _ => break,
         }
     }
-    return trimmed;
+    trimmed
 }
@@ -0,0 +1,348 @@
+use std::collections::HashMap;
+
+use aheui_core::{BorrowedCode, Cursor, Oper, OwnedCode, Select, TransitionTable};
+use proc_macro2::{Ident, Literal, Span, TokenStream};
+use quote::{quote, ToTokens};
+
+/// 커서의 위치와 방향, 그리고 선택된 저장공간
+///
+/// 선택된 저장공간은 `ㅅ`닿소리 명령으로만 바뀌므로, 상태마다 정해져 있습니다.
+/// `ㅎ`통로는 연결되지 않으므로 선택될 수 없습니다.
+#[derive(Copy, Clone, Hash, Eq, PartialEq)]
+struct State {
+    position: usize,
+    select: Select,
+}
+
+/// 명령을 실행한 뒤 진행하는 상태와 뒤집혀 진행하는 상태
+struct Edges {
+    forward: Option<usize>,
+    reverse: Option<usize>,
+}
+
+/// 시작 상태에서 갈 수 있는 모든 상태와, 상태 사이의 이동
+struct Graph {
+    table: TransitionTable,
+    /// 첫 상태가 시작 상태입니다.
+    states: Vec<State>,
+    edges: Vec<Edges>,
+}
+
+impl Graph {
+    /// 코드가 비어있으면 `None`을 돌려줍니다.
+    fn new(code: &OwnedCode) -> Option<Graph> {
+        let table = TransitionTable::new(&BorrowedCode::from(code));
+        let start = State {
+            position: table.position_of(Cursor::new())?,
+            select: Select::Stack(0),
+        };
+
+        let mut states = vec![start];
+        let mut indices = HashMap::new();
+        indices.insert(start, 0);
+        let mut edges: Vec<Edges> = Vec::new();
+        while edges.len() < states.len() {
+            let State { position, select } = states[edges.len()];
+            let (forward, reverse) = successors(&table, position, select);
+            let mut index_of = |state: State| {
+                *indices.entry(state).or_insert_with(|| {
+                    states.push(state);
+                    states.len() - 1
+                })
+            };
+            edges.push(Edges {
+                forward: forward.map(&mut index_of),
+                reverse: reverse.map(&mut index_of),
+            });
+        }
+        Some(Graph {
+            table,
+            states,
+            edges,
+        })
+    }
+}
+
+/// 코드가 비어있지 않고, 실행 중에 `ㅂ`닿소리의 `ㅇ`, `ㅎ`받침 명령을 만나 입력을 읽는 일이
+/// 없는지 확인합니다.
+pub fn is_input_free(code: &OwnedCode) -> bool {
+    let graph = match Graph::new(code) {
+        Some(graph) => graph,
+        None => return false,
+    };
+    graph.states.iter().all(|state| {
+        !matches!(
+            graph.table.inst(state.position).oper,
+            Oper::ReadChar | Oper::ReadInt
+        )
+    })
+}
+
+/// 코드의 흐름을 블록 단위의 상태 기계로 옮긴 Rust 코드를 만듭니다.
+/// 저장공간은 지역 변수가 되고, 블록 안의 명령들은 곧게 이어집니다.
+///
+/// 만들어진 코드는 `input`에서 읽고 `output`에 쓰며, 종료 코드를 `result`에 담습니다.
+/// 코드가 비어있으면 `None`을 돌려줍니다.
+pub fn generate(code: &OwnedCode, cell: &TokenStream) -> Option<TokenStream> {
+    let Graph {
+        table,
+        states,
+        edges,
+    } = Graph::new(code)?;
+
+    // 진행해서만 들어올 수 있는 상태는 이전 상태의 블록에 이어 붙입니다.
+    let mut incoming = vec![0; states.len()];
+    incoming[0] += 1;
+    let mut forward_from = vec![None; states.len()];
+    for (from, edge) in edges.iter().enumerate() {
+        if let Some(to) = edge.forward {
+            incoming[to] += 1;
+            forward_from[to] = Some(from);
+        }
+        if let Some(to) = edge.reverse {
+            incoming[to] += 1;
+        }
+    }
+    let is_head = |state: usize| incoming[state] != 1 || forward_from[state].is_none();
+    let heads: Vec<usize> = (0..states.len()).filter(|&state| is_head(state)).collect();
+    let mut block_ids = HashMap::new();
+    for (id, &head) in heads.iter().enumerate() {
+        block_ids.insert(head, id);
+    }
+    let jump = |state: usize| {
+        let id = Literal::usize_unsuffixed(block_ids[&state]);
+        quote! { state = #id; }
+    };
+
+    let mut arms = Vec::new();
+    for &head in heads.iter() {
+        let mut body = TokenStream::new();
+        let mut state = head;
+        loop {
+            let reverse = edges[state].reverse.map(&jump);
+            let reverse = quote! { { #reverse continue; } };
+            body.extend(instruction(&table, states[state], &reverse, cell));
+            match edges[state].forward {
+                Some(next) if !is_head(next) => state = next,
+                Some(next) => {
+                    body.extend(jump(next));
+                    break;
+                }
+                None => break,
+            }
+        }
+        let id = Literal::usize_unsuffixed(block_ids[&head]);
+        arms.push(quote! { #id => { #body } });
+    }
+
+    let mut storages = Vec::new();
+    for state in states.iter() {
+        storages.push(state.select);
+        if let Oper::Move(target) = table.inst(state.position).oper {
+            storages.push(target);
+        }
+    }
+    storages.sort_by_key(|select| match select {
+        Select::Stack(id) => *id as u32,
+        _ => u32::MAX,
+    });
+    storages.dedup();
+    let declarations = storages.iter().filter_map(|&select| match select {
+        Select::Stack(_) => {
+            let ident = storage(select);
+            Some(quote! { let mut #ident: ::std::vec::Vec<#cell> = ::std::vec::Vec::new(); })
+        }
+        Select::Queue => Some(quote! {
+            let mut queue: ::std::collections::VecDeque<#cell> =
+                ::std::collections::VecDeque::new();
+        }),
+        Select::Channel => None,
+    });
+
+    Some(quote! {
+        #(#declarations)*
+        let mut state: usize = 0;
+        let result: #cell = loop {
+            match state {
+                #(#arms)*
+                _ => unreachable!(),
+            }
+        };
+    })
+}
+
+fn successors(
+    table: &TransitionTable,
+    position: usize,
+    select: Select,
+) -> (Option<State>, Option<State>) {
+    let forward = |select| State {
+        position: table.next(position, false),
+        select,
+    };
+    let reverse = State {
+        position: table.next(position, true),
+        select,
+    };
+    match table.inst(position).oper {
+        Oper::Halt | Oper::Select(Select::Channel) | Oper::Move(Select::Channel) => (None, None),
+        Oper::Select(select) => (Some(forward(select)), None),
+        Oper::Nop | Oper::Push(_) | Oper::ReadChar | Oper::ReadInt => (Some(forward(select)), None),
+        _ => (Some(forward(select)), Some(reverse)),
+    }
+}
+
+fn storage(select: Select) -> Ident {
+    match select {
+        Select::Stack(id) => Ident::new(&format!("stack{}", id), Span::call_site()),
+        _ => Ident::new("queue", Span::call_site()),
+    }
+}
+
+fn pop(select: Select) -> TokenStream {
+    let storage = storage(select);
+    match select {
+        Select::Queue => quote! { #storage.pop_front() },
+        _ => quote! { #storage.pop() },
+    }
+}
+
+fn push(select: Select, value: TokenStream) -> TokenStream {
+    let storage = storage(select);
+    match select {
+        Select::Queue => quote! { #storage.push_back(#value) },
+        _ => quote! { #storage.push(#value) },
+    }
+}
+
+fn peek(select: Select) -> TokenStream {
+    let storage = storage(select);
+    match select {
+        Select::Queue => quote! { #storage.front().copied() },
+        _ => quote! { #storage.last().copied() },
+    }
+}
+
+/// `state`의 명령을 실행하는 코드. 커서가 뒤집혀야 하면 `reverse`를 실행합니다.
+fn instruction(
+    table: &TransitionTable,
+    state: State,
+    reverse: &TokenStream,
+    cell: &TokenStream,
+) -> TokenStream {
+    let address = table.cursor(state.position).address;
+    let (row, col) = (address.row, address.col);
+    let select = state.select;
+    let storage = storage(select);
+    let (pop, peek) = (pop(select), peek(select));
+    match table.inst(state.position).oper {
+        Oper::Nop | Oper::Select(Select::Stack(_)) | Oper::Select(Select::Queue) => quote! {},
+        Oper::Halt => quote! { break #pop.unwrap_or(0); },
+        Oper::Add | Oper::Mul | Oper::Sub | Oper::Div | Oper::Mod | Oper::Compare => {
+            let value = match table.inst(state.position).oper {
+                Oper::Add => quote! { second.wrapping_add(first) },
+                Oper::Mul => quote! { second.wrapping_mul(first) },
+                Oper::Sub => quote! { second.wrapping_sub(first) },
+                Oper::Div => quote! { second.wrapping_div(first) },
+                Oper::Mod => quote! { second.wrapping_rem(first) },
+                _ => quote! { (first <= second) as #cell },
+            };
+            let check = match table.inst(state.position).oper {
+                Oper::Div | Oper::Mod => quote! {
+                    if first == 0 {
+                        ::aheui_core::native::fail::<#cell>(
+                            #row, #col, ::aheui_core::ExecErrorKind::DivisionByZero,
+                        );
+                    }
+                },
+                _ => quote! {},
+            };
+            let push = push(select, value);
+            quote! {
+                if #storage.len() < 2 #reverse
+                let first = #pop.unwrap();
+                let second = #pop.unwrap();
+                #check
+                #push;
+            }
+        }
+        Oper::WriteChar => quote! {
+            match #peek {
+                Some(value) => {
+                    ::aheui_core::native::write_char(&mut output, value, #row, #col);
+                    #pop;
+                }
+                None => #reverse
+            }
+        },
+        Oper::WriteInt => quote! {
+            match #pop {
+                Some(value) => ::aheui_core::native::write_int(&mut output, value, #row, #col),
+                None => #reverse
+            }
+        },
+        Oper::Pop => quote! {
+            if #pop.is_none() #reverse
+        },
+        Oper::ReadChar | Oper::ReadInt => {
+            let read = match table.inst(state.position).oper {
+                Oper::ReadChar => quote! { read_char },
+                _ => quote! { read_int },
+            };
+            let push = push(select, quote! { value });
+            quote! {
+                let value = ::aheui_core::native::#read::<#cell>(&mut input, #row, #col);
+                #push;
+            }
+        }
+        Oper::Push(value) => {
+            let push = push(select, Literal::u8_unsuffixed(value).into_token_stream());
+            quote! { #push; }
+        }
+        Oper::Dup => {
+            let dup = match select {
+                Select::Queue => quote! { #storage.push_front(value) },
+                _ => quote! { #storage.push(value) },
+            };
+            quote! {
+                match #peek {
+                    Some(value) => #dup,
+                    None => #reverse
+                }
+            }
+        }
+        Oper::Swap => {
+            let swap = match select {
+                Select::Queue => quote! { #storage.swap(0, 1); },
+                _ => quote! {
+                    let len = #storage.len();
+                    #storage.swap(len - 1, len - 2);
+                },
+            };
+            quote! {
+                if #storage.len() < 2 #reverse
+                #swap
+            }
+        }
+        Oper::Select(Select::Channel) | Oper::Move(Select::Channel) => quote! {
+            ::aheui_core::native::fail::<#cell>(
+                #row, #col, ::aheui_core::ExecErrorKind::NoChannel,
+            );
+        },
+        Oper::Move(target) => {
+            let push = push(target, quote! { value });
+            quote! {
+                match #pop {
+                    Some(value) => #push,
+                    None => #reverse
+                }
+            }
+        }
+        Oper::Cond => quote! {
+            match #pop {
+                Some(value) if value != 0 => {}
+                _ => #reverse
+            }
+        },
+    }
+}
@@ -0,0 +1,478 @@
+use aheui_core::{Address, Cell, Machine, MachineState, Oper, Select, StepEvent};
+use std::io::{self, BufRead, Write};
+
+/// Steps between the checkpoints for going backwards.
+pub const HISTORY_INTERVAL: u64 = 100;
+/// Goes back up to a million steps.
+pub const HISTORY_CHECKPOINTS: usize = 10000;
+/// Values kept in the checkpoints in total, so that programs with large storages don't use up
+/// the memory. They go back fewer steps.
+pub const HISTORY_CELLS: usize = 10_000_000;
+
+const HELP: &str = "\
+commands:
+  s, step [N]           execute N instructions (1 by default)
+  c, continue           run until a breakpoint or the end
+  rs, reverse-step [N]  undo N instructions (1 by default)
+  rc, reverse-continue  run backwards until a breakpoint or the start of the history
+  b, break ROW COL      break before executing the cell at ROW, COL
+  b, break OPER         break before every instruction of the kind, e.g. `break Halt`
+  d, delete [N]         delete the breakpoint N, or all of them
+  l, list               list the breakpoints
+  w, where              print the cursor and the next instruction
+  p, print [STORAGE]    print the selected storage, `stack N`, `queue` or `all`
+  save FILE             write the state of the machine to FILE
+  load FILE             restore the state of the machine from FILE
+  q, quit               stop debugging
+  h, help               print this message";
+
+enum Breakpoint {
+    Address(Address),
+    Oper(String),
+}
+
+impl std::fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Breakpoint::Address(address) => {
+                write!(f, "row {}, column {}", address.row, address.col)
+            }
+            Breakpoint::Oper(name) => write!(f, "every {}", name),
+        }
+    }
+}
+
+enum Stop {
+    Breakpoint(usize),
+    Halted,
+    Error,
+}
+
+pub struct Debugger<'a, T: Cell> {
+    machine: Machine<'a, T>,
+    chars: Vec<Vec<char>>,
+    breakpoints: Vec<Breakpoint>,
+}
+
+impl<'a, T: Cell> Debugger<'a, T> {
+    pub fn new(machine: Machine<'a, T>, source: &str) -> Self {
+        Debugger {
+            machine,
+            chars: source.lines().map(|line| line.chars().collect()).collect(),
+            breakpoints: Vec::new(),
+        }
+    }
+
+    /// Reads commands from `commands` until it's closed or `quit` is given.
+    pub fn run(&mut self, commands: &mut dyn BufRead, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "type `help` for the commands")?;
+        self.print_where(out)?;
+        let mut line = String::new();
+        loop {
+            write!(out, "(aheui) ")?;
+            out.flush()?;
+            line.clear();
+            if commands.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+            if !self.execute_command(line.trim(), out)? {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Returns `false` to quit.
+    fn execute_command(&mut self, line: &str, out: &mut dyn Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["s"] | ["step"] => self.step(1, out)?,
+            ["s", n] | ["step", n] => match n.parse() {
+                Ok(n) => self.step(n, out)?,
+                Err(_) => writeln!(out, "not a number: {}", n)?,
+            },
+            ["c"] | ["continue"] => self.continue_(out)?,
+            ["rs"] | ["reverse-step"] => self.reverse_step(1, out)?,
+            ["rs", n] | ["reverse-step", n] => match n.parse() {
+                Ok(n) => self.reverse_step(n, out)?,
+                Err(_) => writeln!(out, "not a number: {}", n)?,
+            },
+            ["rc"] | ["reverse-continue"] => self.reverse_continue(out)?,
+            ["b", row, col] | ["break", row, col] => match (row.parse(), col.parse()) {
+                (Ok(row), Ok(col)) => {
+                    self.add_breakpoint(Breakpoint::Address(Address { row, col }), out)?
+                }
+                _ => writeln!(out, "not an address: {} {}", row, col)?,
+            },
+            ["b", oper] | ["break", oper] => {
+                match Oper::NAMES
+                    .iter()
+                    .find(|name| name.eq_ignore_ascii_case(oper))
+                {
+                    Some(name) => self.add_breakpoint(Breakpoint::Oper(name.to_string()), out)?,
+                    None => writeln!(out, "unknown instruction: {}", oper)?,
+                }
+            }
+            ["d"] | ["delete"] => {
+                self.breakpoints.clear();
+                writeln!(out, "deleted all breakpoints")?;
+            }
+            ["d", n] | ["delete", n] => match n.parse::<usize>() {
+                Ok(n) if n < self.breakpoints.len() => {
+                    let breakpoint = self.breakpoints.remove(n);
+                    writeln!(out, "deleted breakpoint {}: {}", n, breakpoint)?;
+                }
+                _ => writeln!(out, "no such breakpoint: {}", n)?,
+            },
+            ["l"] | ["list"] => {
+                for (i, breakpoint) in self.breakpoints.iter().enumerate() {
+                    writeln!(out, "{}: {}", i, breakpoint)?;
+                }
+            }
+            ["w"] | ["where"] => self.print_where(out)?,
+            ["p"] | ["print"] => self.print_storage(self.machine.storages().current(), out)?,
+            ["p", "all"] | ["print", "all"] => {
+                for id in 0..27 {
+                    if !self.machine.storages().stack(id).is_empty() {
+                        self.print_storage(Select::Stack(id), out)?;
+                    }
+                }
+                self.print_storage(Select::Queue, out)?;
+            }
+            ["p", "queue"] | ["print", "queue"] => self.print_storage(Select::Queue, out)?,
+            ["p", "stack", id] | ["print", "stack", id] => match id.parse() {
+                Ok(id) if id < 27 && id != 21 => self.print_storage(Select::Stack(id), out)?,
+                _ => writeln!(out, "no such stack: {}", id)?,
+            },
+            ["save", file] => match std::fs::write(file, self.machine.save().to_string()) {
+                Ok(()) => writeln!(out, "saved to {}", file)?,
+                Err(e) => writeln!(out, "error: {}", e)?,
+            },
+            ["load", file] => self.load(file, out)?,
+            ["q"] | ["quit"] => return Ok(false),
+            ["h"] | ["help"] => writeln!(out, "{}", HELP)?,
+            _ => writeln!(out, "unknown command: {}", line)?,
+        }
+        Ok(true)
+    }
+
+    fn add_breakpoint(&mut self, breakpoint: Breakpoint, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "breakpoint {}: {}", self.breakpoints.len(), breakpoint)?;
+        self.breakpoints.push(breakpoint);
+        Ok(())
+    }
+
+    fn step(&mut self, n: u64, out: &mut dyn Write) -> io::Result<()> {
+        for _ in 0..n {
+            if let Some(stop) = self.step_one(out)? {
+                self.print_stop(stop, out)?;
+                return Ok(());
+            }
+        }
+        self.print_where(out)
+    }
+
+    fn continue_(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        // Doesn't stop at the breakpoint it's already stopped at.
+        let mut stop = self.step_one(out)?;
+        while stop.is_none() {
+            stop = match self.hit_breakpoint() {
+                Some(i) => Some(Stop::Breakpoint(i)),
+                None => self.step_one(out)?,
+            };
+        }
+        self.print_stop(stop.unwrap(), out)
+    }
+
+    fn reverse_step(&mut self, n: u64, out: &mut dyn Write) -> io::Result<()> {
+        let target = self.machine.steps().saturating_sub(n);
+        if !self.machine.rewind(target) {
+            writeln!(out, "the history doesn't reach back to step {}", target)?;
+        }
+        self.print_where(out)
+    }
+
+    fn reverse_continue(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        // Scans the steps between two checkpoints forwards at once for the last breakpoint hit,
+        // going back one checkpoint at a time.
+        let mut end = self.machine.steps();
+        while end > 0 {
+            let start = match self.machine.checkpoint_before(end - 1) {
+                Some(start) => start,
+                None => break,
+            };
+            self.machine.rewind(start);
+            let mut hit = None;
+            while self.machine.steps() < end {
+                if let Some(i) = self.hit_breakpoint() {
+                    hit = Some((self.machine.steps(), i));
+                }
+                // The steps have been executed before, so they succeed again.
+                let _ = self.machine.step();
+            }
+            if let Some((steps, i)) = hit {
+                self.machine.rewind(steps);
+                return self.print_stop(Stop::Breakpoint(i), out);
+            }
+            end = start;
+        }
+        self.machine.rewind(end);
+        writeln!(out, "reached the start of the history")?;
+        self.print_where(out)
+    }
+
+    fn load(&mut self, file: &str, out: &mut dyn Write) -> io::Result<()> {
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => return writeln!(out, "error: {}", e),
+        };
+        match text.parse::<MachineState<T>>() {
+            // The input can't be moved back or forth, so it has to be where the state expects.
+            Ok(state) if state.read != self.machine.read() => writeln!(
+                out,
+                "error: the state has read {} bytes of the input, but {} bytes have been read",
+                state.read,
+                self.machine.read()
+            ),
+            Ok(state) => match self.machine.restore(&state) {
+                Ok(()) => self.print_where(out),
+                Err(e) => writeln!(out, "error: {}", e),
+            },
+            Err(e) => writeln!(out, "error: {}", e),
+        }
+    }
+
+    fn step_one(&mut self, out: &mut dyn Write) -> io::Result<Option<Stop>> {
+        match self.machine.step() {
+            Ok(StepEvent::Executed { .. }) => Ok(None),
+            Ok(StepEvent::Halted { .. }) => Ok(Some(Stop::Halted)),
+            Ok(StepEvent::LimitExceeded { limit, .. }) => {
+                writeln!(out, "limit exceeded: {:?}", limit)?;
+                Ok(Some(Stop::Error))
+            }
+            Err(e) => {
+                writeln!(out, "error: {}", e)?;
+                Ok(Some(Stop::Error))
+            }
+        }
+    }
+
+    fn hit_breakpoint(&self) -> Option<usize> {
+        let address = self.machine.cursor().address;
+        let name = self.machine.inst().oper.name();
+        self.breakpoints.iter().position(|b| match b {
+            Breakpoint::Address(a) => *a == address,
+            Breakpoint::Oper(oper) => oper.eq_ignore_ascii_case(name),
+        })
+    }
+
+    fn print_stop(&self, stop: Stop, out: &mut dyn Write) -> io::Result<()> {
+        match stop {
+            Stop::Breakpoint(i) => writeln!(out, "breakpoint {}: {}", i, self.breakpoints[i])?,
+            Stop::Halted => {
+                let value = self.machine.halted().unwrap();
+                return writeln!(out, "halted with {}", value);
+            }
+            Stop::Error => {}
+        }
+        self.print_where(out)
+    }
+
+    fn print_where(&self, out: &mut dyn Write) -> io::Result<()> {
+        let cursor = self.machine.cursor();
+        let address = cursor.address;
+        let ch = self
+            .chars
+            .get(address.row as usize)
+            .and_then(|line| line.get(address.col as usize))
+            .copied()
+            .unwrap_or(' ');
+        let inst = self.machine.inst();
+        writeln!(
+            out,
+            "step {}: row {}, column {}, moving {:?}, next `{}` {:?} {:?}, selected {:?}",
+            self.machine.steps(),
+            address.row,
+            address.col,
+            cursor.step,
+            ch,
+            inst.cursor_control,
+            inst.oper,
+            self.machine.storages().current(),
+        )
+    }
+
+    fn print_storage(&self, select: Select, out: &mut dyn Write) -> io::Result<()> {
+        let storages = self.machine.storages();
+        let (name, values): (String, Vec<String>) = match select {
+            Select::Stack(id) => (
+                format!("stack {} (bottom to top)", id),
+                storages.stack(id).iter().map(ToString::to_string).collect(),
+            ),
+            Select::Queue => (
+                "queue (front to back)".to_string(),
+                storages.queue().iter().map(ToString::to_string).collect(),
+            ),
+            Select::Channel => return writeln!(out, "the channel is not visible"),
+        };
+        writeln!(out, "{}: [{}]", name, values.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aheui_core::{Env, OwnedCode};
+
+    /// Pushes 8 and 6, adds them, prints 14 and halts with 14.
+    const SOURCE: &str = "밣밦다빠망희";
+
+    /// Runs the debugger with `commands`, and returns what it has printed.
+    fn debug(commands: &str) -> String {
+        let code = OwnedCode::parse(SOURCE);
+        let mut input = io::empty();
+        let mut output = Vec::new();
+        // Checkpoints every 2 steps, so that going back crosses them.
+        let env = Env::new(&code, &mut input, &mut output).with_history(2, HISTORY_CHECKPOINTS);
+        let mut debugger = Debugger::new(Machine::new(env), SOURCE);
+        let mut transcript = Vec::new();
+        debugger
+            .run(&mut commands.as_bytes(), &mut transcript)
+            .unwrap();
+        String::from_utf8(transcript).unwrap()
+    }
+
+    const START: &str = "type `help` for the commands\n\
+         step 0: row 0, column 0, moving Row(1), next `밣` Right Push(8), selected Stack(0)\n";
+
+    #[test]
+    fn test_step() {
+        assert_eq!(
+            debug("s\nstep 2\n"),
+            START.to_string()
+                + "(aheui) step 1: row 0, column 1, moving Column(1), next `밦` Right Push(6), \
+                   selected Stack(0)\n\
+                   (aheui) step 3: row 0, column 3, moving Column(1), next `빠` Right Dup, \
+                   selected Stack(0)\n\
+                   (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_continue_to_breakpoint() {
+        assert_eq!(
+            debug("b 0 3\nc\nb writeint\nc\nc\n"),
+            START.to_string()
+                + "(aheui) breakpoint 0: row 0, column 3\n\
+                   (aheui) breakpoint 0: row 0, column 3\n\
+                   step 3: row 0, column 3, moving Column(1), next `빠` Right Dup, \
+                   selected Stack(0)\n\
+                   (aheui) breakpoint 1: every WriteInt\n\
+                   (aheui) breakpoint 1: every WriteInt\n\
+                   step 4: row 0, column 4, moving Column(1), next `망` Right WriteInt, \
+                   selected Stack(0)\n\
+                   (aheui) halted with 14\n\
+                   (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_reverse_continue() {
+        assert_eq!(
+            debug("c\nb push\nrc\nrc\nrc\n"),
+            START.to_string()
+                + "(aheui) halted with 14\n\
+                   (aheui) breakpoint 0: every Push\n\
+                   (aheui) breakpoint 0: every Push\n\
+                   step 1: row 0, column 1, moving Column(1), next `밦` Right Push(6), \
+                   selected Stack(0)\n\
+                   (aheui) breakpoint 0: every Push\n\
+                   step 0: row 0, column 0, moving Row(1), next `밣` Right Push(8), \
+                   selected Stack(0)\n\
+                   (aheui) reached the start of the history\n\
+                   step 0: row 0, column 0, moving Row(1), next `밣` Right Push(8), \
+                   selected Stack(0)\n\
+                   (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_print() {
+        assert_eq!(
+            debug("s 3\np\np stack 1\np queue\np all\n"),
+            START.to_string()
+                + "(aheui) step 3: row 0, column 3, moving Column(1), next `빠` Right Dup, \
+                   selected Stack(0)\n\
+                   (aheui) stack 0 (bottom to top): [14]\n\
+                   (aheui) stack 1 (bottom to top): []\n\
+                   (aheui) queue (front to back): []\n\
+                   (aheui) stack 0 (bottom to top): [14]\n\
+                   queue (front to back): []\n\
+                   (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_delete() {
+        assert_eq!(
+            debug("b 0 3\nb halt\nl\nd 0\nl\nc\nd\nl\n"),
+            START.to_string()
+                + "(aheui) breakpoint 0: row 0, column 3\n\
+                   (aheui) breakpoint 1: every Halt\n\
+                   (aheui) 0: row 0, column 3\n\
+                   1: every Halt\n\
+                   (aheui) deleted breakpoint 0: row 0, column 3\n\
+                   (aheui) 0: every Halt\n\
+                   (aheui) breakpoint 0: every Halt\n\
+                   step 5: row 0, column 5, moving Column(1), next `희` Mirror Halt, \
+                   selected Stack(0)\n\
+                   (aheui) deleted all breakpoints\n\
+                   (aheui) (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = std::env::temp_dir();
+        let file = dir.join(format!("aheui-debug-{}.state", std::process::id()));
+        let file = file.to_str().unwrap();
+        let transcript = debug(&format!("s\nsave {0}\ns 2\nload {0}\n", file));
+        assert!(transcript.ends_with(&format!(
+            "(aheui) saved to {}\n\
+             (aheui) step 3: row 0, column 3, moving Column(1), next `빠` Right Dup, \
+             selected Stack(0)\n\
+             (aheui) step 1: row 0, column 1, moving Column(1), next `밦` Right Push(6), \
+             selected Stack(0)\n\
+             (aheui) ",
+            file
+        )));
+
+        let state = std::fs::read_to_string(file).unwrap();
+        std::fs::write(file, state.replace("read 0", "read 3")).unwrap();
+        let transcript = debug(&format!("load {}\n", file));
+        std::fs::remove_file(file).unwrap();
+        assert_eq!(
+            transcript,
+            START.to_string()
+                + "(aheui) error: the state has read 3 bytes of the input, \
+                   but 0 bytes have been read\n\
+                   (aheui) "
+        );
+    }
+
+    #[test]
+    fn test_invalid_input() {
+        assert_eq!(
+            debug("foo\ns x\nb Hlat\nb 3\nb 0 x\nd 0\np stack 21\nl\n"),
+            START.to_string()
+                + "(aheui) unknown command: foo\n\
+                   (aheui) not a number: x\n\
+                   (aheui) unknown instruction: Hlat\n\
+                   (aheui) unknown instruction: 3\n\
+                   (aheui) not an address: 0 x\n\
+                   (aheui) no such breakpoint: 0\n\
+                   (aheui) no such stack: 21\n\
+                   (aheui) (aheui) "
+        );
+    }
+}
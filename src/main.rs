@@ -1,15 +1,177 @@
-use aheui_core::{Env, OwnedCode};
-use std::io::Write;
+use aheui_core::{Cell, Engine, Env, Machine, MemoryChannel, Outcome, OwnedCode, Profiler};
+use std::io::{BufRead, IsTerminal, Write};
+
+mod debug;
+mod trace;
+
+use debug::Debugger;
+use trace::Tracer;
+
+#[cfg(feature = "bigint")]
+type Value = aheui_core::BigInt;
+#[cfg(not(feature = "bigint"))]
+type Value = i32;
+
+const USAGE: &str = "\
+usage: aheui-rs [--trace[=FILE]] [--profile] [--detect-loops] [--compile] [--input=FILE] FILE
+       aheui-rs debug [--input=FILE] FILE";
+
+enum TraceTarget {
+    Stderr,
+    File(String),
+}
+
+struct Args {
+    debug: bool,
+    path: String,
+    /// Read by the program instead of stdin.
+    input: Option<String>,
+    trace: Option<TraceTarget>,
+    profile: bool,
+    detect_loops: bool,
+    /// Runs with `Engine::Compiled`.
+    compile: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = std::env::args().skip(1).peekable();
+    let debug = args.next_if_eq("debug").is_some();
+    let mut path = None;
+    let mut input = None;
+    let mut trace = None;
+    let mut profile = false;
+    let mut detect_loops = false;
+    let mut compile = false;
+    for arg in args {
+        if let Some(file) = arg.strip_prefix("--input=") {
+            input = Some(file.to_string());
+        } else if debug && arg.starts_with("--") {
+            return Err(format!("unknown option for debug: {}", arg));
+        } else if arg == "--trace" {
+            trace = Some(TraceTarget::Stderr);
+        } else if let Some(file) = arg.strip_prefix("--trace=") {
+            trace = Some(TraceTarget::File(file.to_string()));
+        } else if arg == "--profile" {
+            profile = true;
+        } else if arg == "--detect-loops" {
+            detect_loops = true;
+        } else if arg == "--compile" {
+            compile = true;
+        } else if arg.starts_with("--") {
+            return Err(format!("unknown option: {}", arg));
+        } else if path.replace(arg).is_some() {
+            return Err("too many files".to_string());
+        }
+    }
+    let path = path.ok_or_else(|| "no file is given".to_string())?;
+    Ok(Args {
+        debug,
+        path,
+        input,
+        trace,
+        profile,
+        detect_loops,
+        compile,
+    })
+}
 
 fn main() {
-    let input_file = std::env::args().nth(1).unwrap();
-    let input = std::fs::read_to_string(input_file).unwrap();
-    let code = OwnedCode::parse(&input);
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
+    let source = std::fs::read_to_string(&args.path).unwrap();
+    let code = OwnedCode::parse(&source);
     let stdin = std::io::stdin();
     let stdout = std::io::stdout();
-    let mut input = stdin.lock();
+    let mut input: Box<dyn BufRead> = match &args.input {
+        Some(file) => match std::fs::File::open(file) {
+            Ok(file) => Box::new(std::io::BufReader::new(file)),
+            Err(e) => {
+                eprintln!("error: cannot open the input file {}: {}", file, e);
+                std::process::exit(2);
+            }
+        },
+        // The debugger reads commands from stdin.
+        None if args.debug => Box::new(std::io::empty()),
+        None => Box::new(stdin.lock()),
+    };
     let mut output = stdout.lock();
-    let result = Env::new(&code, &mut input, &mut output).execute();
+    let mut channel = MemoryChannel::new();
+    let mut profiler = Profiler::new();
+    let mut env =
+        Env::<Value>::new_with_cell(&code, &mut input, &mut output).with_channel(&mut channel);
+    if args.profile {
+        env = env.with_observer(&mut profiler);
+    }
+    if args.detect_loops {
+        env = env.with_loop_detection();
+    }
+    if args.compile {
+        env = env.with_engine(Engine::Compiled);
+    }
+    if args.debug {
+        let env = env
+            .with_history(debug::HISTORY_INTERVAL, debug::HISTORY_CHECKPOINTS)
+            .with_history_cell_limit(debug::HISTORY_CELLS);
+        let mut debugger = Debugger::new(Machine::new(env), &source);
+        debugger
+            .run(&mut stdin.lock(), &mut std::io::stdout())
+            .unwrap();
+        return;
+    }
+    let result = match &args.trace {
+        None => env.try_execute(),
+        Some(target) => {
+            let out: Box<dyn Write> = match target {
+                TraceTarget::Stderr => Box::new(std::io::stderr()),
+                TraceTarget::File(file) => match std::fs::File::create(file) {
+                    Ok(file) => Box::new(std::io::BufWriter::new(file)),
+                    Err(e) => {
+                        eprintln!("error: cannot create the trace file {}: {}", file, e);
+                        std::process::exit(2);
+                    }
+                },
+            };
+            let mut tracer = Tracer::new(&source, out);
+            match tracer
+                .run(Machine::new(env))
+                .and_then(|result| tracer.flush().map(|()| result))
+            {
+                Ok(result) => result,
+                Err(e) => {
+                    eprintln!("error: cannot write the trace: {}", e);
+                    std::process::exit(2);
+                }
+            }
+        }
+    };
     output.flush().unwrap();
-    std::process::exit(result);
+    if args.profile {
+        let stderr = std::io::stderr();
+        let report = profiler.render(&source, stderr.is_terminal());
+        stderr.lock().write_all(report.as_bytes()).unwrap();
+    }
+    match result {
+        Ok(Outcome::Halted(value)) => std::process::exit(value.to_i32_wrapping()),
+        Ok(Outcome::InfiniteLoop { cycle, .. }) => {
+            let cells: Vec<String> = cycle
+                .iter()
+                .map(|address| format!("({}, {})", address.row, address.col))
+                .collect();
+            eprintln!("error: infinite loop through {}", cells.join(" "));
+            std::process::exit(-1);
+        }
+        Ok(outcome) => {
+            eprintln!("error: the execution has stopped: {:?}", outcome);
+            std::process::exit(-1);
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(-1);
+        }
+    }
 }
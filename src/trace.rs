@@ -0,0 +1,262 @@
+use aheui_core::{
+    Address, Cell, ExecError, Inst, Machine, Outcome, Select, StepEvent, StorageSelector,
+};
+use std::io::{self, Write};
+
+/// Writes one JSON object per executed step.
+pub struct Tracer<'a> {
+    chars: Vec<Vec<char>>,
+    out: Box<dyn Write + 'a>,
+}
+
+impl<'a> Tracer<'a> {
+    pub fn new(source: &str, out: Box<dyn Write + 'a>) -> Self {
+        Tracer {
+            chars: source.lines().map(|line| line.chars().collect()).collect(),
+            out,
+        }
+    }
+
+    /// Runs `machine` step by step until it stops like `Machine::run()`, writing every step.
+    /// Fails with the outer error if the trace can't be written.
+    pub fn run<T: Cell>(
+        &mut self,
+        mut machine: Machine<'_, T>,
+    ) -> io::Result<Result<Outcome<T>, ExecError<T>>> {
+        loop {
+            if let Some(value) = machine.halted() {
+                return Ok(Ok(Outcome::Halted(value)));
+            }
+            if let Some(outcome) = machine.check_stop() {
+                return Ok(Ok(outcome));
+            }
+            let inst = machine.inst();
+            let select = machine.storages().current();
+            let (address, reversed, halted) = match machine.step() {
+                Ok(StepEvent::Executed {
+                    address, reversed, ..
+                }) => (address, reversed, None),
+                Ok(StepEvent::Halted { address, value }) => (address, false, Some(value)),
+                Ok(StepEvent::LimitExceeded { limit, .. }) => {
+                    return Ok(Ok(Outcome::LimitExceeded {
+                        limit,
+                        steps: machine.steps(),
+                        cursor: machine.cursor(),
+                        storages: machine.storages().snapshot(),
+                    }));
+                }
+                Err(e) => return Ok(Err(e)),
+            };
+            self.write_step(
+                machine.steps(),
+                address,
+                inst,
+                select,
+                reversed,
+                machine.storages(),
+            )?;
+            if let Some(value) = halted {
+                return Ok(Ok(Outcome::Halted(value)));
+            }
+            if let Some(outcome) = machine.check_loop() {
+                return Ok(Ok(outcome));
+            }
+        }
+    }
+
+    pub fn write_step<T: Cell>(
+        &mut self,
+        step: u64,
+        address: Address,
+        inst: Inst,
+        select: Select,
+        reversed: bool,
+        storages: &StorageSelector<'_, T>,
+    ) -> io::Result<()> {
+        let ch = self
+            .chars
+            .get(address.row as usize)
+            .and_then(|line| line.get(address.col as usize))
+            .copied()
+            .unwrap_or(' ');
+        let top = top_values(storages, select)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(",");
+        writeln!(
+            self.out,
+            r#"{{"step":{},"row":{},"col":{},"char":{},"cursor_control":{},"oper":{},"select":{},"reversed":{},"top":[{}]}}"#,
+            step,
+            address.row,
+            address.col,
+            json_string(&ch.to_string()),
+            json_string(&format!("{:?}", inst.cursor_control)),
+            json_string(&format!("{:?}", inst.oper)),
+            json_string(&format!("{:?}", select)),
+            reversed,
+            top,
+        )
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// Up to two values that would be popped next from `select`, in the popping order.
+/// The contents of the channel are not visible.
+fn top_values<T: Cell>(storages: &StorageSelector<'_, T>, select: Select) -> Vec<T> {
+    match select {
+        Select::Stack(id) => storages.stack(id).iter().rev().take(2).cloned().collect(),
+        Select::Queue => storages.queue().iter().take(2).cloned().collect(),
+        Select::Channel => Vec::new(),
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut buf = String::with_capacity(s.len() + 2);
+    buf.push('"');
+    for ch in s.chars() {
+        match ch {
+            '"' => buf.push_str("\\\""),
+            '\\' => buf.push_str("\\\\"),
+            '\n' => buf.push_str("\\n"),
+            '\r' => buf.push_str("\\r"),
+            '\t' => buf.push_str("\\t"),
+            ch if (ch as u32) < 0x20 => buf.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => buf.push(ch),
+        }
+    }
+    buf.push('"');
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aheui_core::{Env, Limit, OwnedCode};
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("아희"), r#""아희""#);
+        assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+        assert_eq!(json_string(r"a\b"), r#""a\\b""#);
+        assert_eq!(json_string("\n\r\t"), r#""\n\r\t""#);
+        assert_eq!(json_string("\u{0}\u{1f}"), r#""\u0000\u001f""#);
+    }
+
+    #[test]
+    fn test_trace() {
+        let source = "밝망희";
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        let machine = Machine::new(Env::new(&code, &mut input, &mut output));
+        let result = Tracer::new(source, Box::new(&mut trace)).run(machine);
+        assert_eq!(result.unwrap().unwrap(), Outcome::Halted(0));
+        assert_eq!(
+            String::from_utf8(trace).unwrap(),
+            concat!(
+                r#"{"step":1,"row":0,"col":0,"char":"밝","cursor_control":"Right","#,
+                r#""oper":"Push(7)","select":"Stack(0)","reversed":false,"top":[7]}"#,
+                "\n",
+                r#"{"step":2,"row":0,"col":1,"char":"망","cursor_control":"Right","#,
+                r#""oper":"WriteInt","select":"Stack(0)","reversed":false,"top":[]}"#,
+                "\n",
+                r#"{"step":3,"row":0,"col":2,"char":"희","cursor_control":"Mirror","#,
+                r#""oper":"Halt","select":"Stack(0)","reversed":false,"top":[]}"#,
+                "\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_trace_empty() {
+        let code = OwnedCode::parse("");
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        let machine = Machine::new(Env::new(&code, &mut input, &mut output));
+        let result = Tracer::new("", Box::new(&mut trace)).run(machine);
+        assert_eq!(result.unwrap().unwrap(), Outcome::Halted(0));
+        assert!(trace.is_empty());
+    }
+
+    #[test]
+    fn test_trace_limit() {
+        let source = "밝밝밝희";
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        let env = Env::new(&code, &mut input, &mut output).with_cell_limit(2);
+        let result = Tracer::new(source, Box::new(&mut trace)).run(Machine::new(env));
+        match result.unwrap().unwrap() {
+            Outcome::LimitExceeded { limit, steps, .. } => {
+                assert_eq!(limit, Limit::Cells);
+                assert_eq!(steps, 2);
+            }
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+        assert_eq!(trace.iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+
+    #[test]
+    fn test_trace_loop() {
+        let source = "아";
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        let env = Env::new(&code, &mut input, &mut output).with_loop_detection();
+        let result = Tracer::new(source, Box::new(&mut trace)).run(Machine::new(env));
+        match result.unwrap().unwrap() {
+            Outcome::InfiniteLoop { steps, cycle, .. } => {
+                assert_eq!(cycle, vec![Address { row: 0, col: 0 }]);
+                assert_eq!(trace.iter().filter(|&&b| b == b'\n').count() as u64, steps);
+            }
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+    }
+
+    #[test]
+    fn test_trace_fuel() {
+        let source = "아";
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let mut trace = Vec::new();
+        let env = Env::new(&code, &mut input, &mut output).with_fuel(5);
+        let result = Tracer::new(source, Box::new(&mut trace)).run(Machine::new(env));
+        match result.unwrap().unwrap() {
+            Outcome::FuelExhausted { steps, .. } => assert_eq!(steps, 5),
+            outcome => panic!("unexpected outcome: {:?}", outcome),
+        }
+        assert_eq!(trace.iter().filter(|&&b| b == b'\n').count(), 5);
+    }
+
+    #[test]
+    fn test_trace_write_error() {
+        struct Failing;
+
+        impl Write for Failing {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("disk full"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let source = "밝망희";
+        let code = OwnedCode::parse(source);
+        let mut input = std::io::empty();
+        let mut output = Vec::new();
+        let machine = Machine::new(Env::new(&code, &mut input, &mut output));
+        let result = Tracer::new(source, Box::new(Failing)).run(machine);
+        assert_eq!(result.unwrap_err().to_string(), "disk full");
+    }
+}
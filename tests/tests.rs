@@ -1,3 +1,5 @@
+#![allow(uncommon_codepoints)]
+
 use aheui_macro::아희;
 
 /// 종료 코드를 리턴합니다.
@@ -134,3 +136,246 @@ fn test_alt_quotes() {
         assert_eq!("안녕하세요?\n", &func());
     }
 }
+
+/// 저장공간의 정수 타입은 반환 타입을 따릅니다.
+#[아희]
+fn nine_to_the_tenth() -> i64 {
+    밞빠빠빠빠빠빠빠빠빠따따따따따따따따따희
+}
+
+#[아희]
+fn nine_to_the_thirty_second() -> (String, i128) {
+    밞빠따빠따빠따빠따빠따희
+}
+
+#[아희]
+fn nine_to_the_twentieth() -> usize {
+    밞빠빠빠빠빠빠빠빠빠빠빠빠빠빠빠빠빠빠빠따따따따따따따따따따따따따따따따따따따희
+}
+
+#[test]
+fn test_cell_type() {
+    assert_eq!(3486784401, nine_to_the_tenth());
+    assert_eq!(12157665459056928801, nine_to_the_twentieth());
+    assert_eq!(
+        (String::new(), 3433683820292512484657849089281),
+        nine_to_the_thirty_second()
+    );
+}
+
+/// 입력을 읽지 않는 코드는 컴파일 중에 실행되고, 너무 오래 걸리면 실행 시간에 실행됩니다.
+#[아희]
+fn count_down_long() -> (i32, String) {
+    붒ㅇㅇㅇ;
+    붋ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붋ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붋ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붋ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붋ㅇㅇ희;
+    뚜아빠추;
+    ㅇㅇㅇ북;
+    아오ㅇ터;
+}
+
+#[아희]
+fn division_by_zero() -> i32 {
+    받반반타나희
+}
+
+#[test]
+fn test_evaluated() {
+    assert_eq!((0, String::new()), count_down_long());
+}
+
+#[test]
+#[should_panic(expected = "division by zero")]
+fn test_evaluated_division_by_zero() {
+    division_by_zero();
+}
+
+/// 코드를 Rust 코드로 옮겨 함께 컴파일할 수 있습니다.
+#[아희(컴파일=네이티브)]
+fn the_answer_native() -> i32 {
+    밦밠따희
+}
+
+#[아희(컴파일=네이티브)]
+fn hello_world_native() -> String {
+    밤밣따빠밣밟따뿌;
+    빠맣파빨받밤뚜뭏;
+    돋밬탕빠맣붏두붇;
+    볻뫃박발뚷투뭏붖;
+    뫃도뫃희멓뭏뭏붘;
+    뫃봌토범더벌뿌뚜;
+    뽑뽀멓멓더벓뻐뚠;
+    뽀덩벐멓뻐덕더벅;
+}
+
+#[아희(컴파일=네이티브)]
+fn fibo_native() -> (i32, String) {
+    반반나빠빠쌈다빠망빠쌈삼파싸사빠발발밖따따쟈하처우;
+    ㅇㅇㅇㅇㅇㅇ오어어어어어어어어어어어어어어어어어어
+}
+
+#[아희(입력=인자(input), 컴파일=네이티브)]
+fn codepoint_native(input: &str) -> String {
+    밯망희
+}
+
+#[아희(인용=문자열, 컴파일=네이티브)]
+fn alt_quote_native() -> String {
+    "
+    어듀벊벖버범벅벖떠벋벍떠벑번뻐버떠뻐벚벌버더벊벖떠벛벜버버
+    　ㅇ　　ㅏㄴㄴㅕㅇ　　ㅎ　　ㅏ　ㅅ　　ㅔ　ㅇ　　ㅛ　　　\\0
+    　뿌멓더떠떠떠떠더벋떠벌뻐뻐뻐
+    붉차밠밪따따다밠밨따따다　박봃
+    받빠따따맣반발따맣아희～
+    ";
+}
+
+#[아희(컴파일=네이티브)]
+fn nine_to_the_thirty_second_native() -> (String, i128) {
+    밞빠따빠따빠따빠따빠따희
+}
+
+/// 큐에 넣은 값들로 계산합니다.
+#[아희(컴파일=네이티브)]
+fn queue_native() -> String {
+    상발받밤타망망희
+}
+
+/// 결과는 인터프리터로 실행한 것과 같습니다.
+#[아희]
+fn count_down() -> (i32, String) {
+    붒ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇ희;
+    뚜아빠추;
+    ㅇㅇㅇ북;
+    아오ㅇ터;
+}
+
+#[아희(컴파일=네이티브)]
+fn count_down_native() -> (i32, String) {
+    붒ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇㅇ;
+    뚜ㅇㅇㅇ;
+    붒ㅇㅇ희;
+    뚜아빠추;
+    ㅇㅇㅇ북;
+    아오ㅇ터;
+}
+
+/// 값이 모자라 커서가 뒤집히는 경우도 인터프리터와 같습니다.
+#[아희]
+fn underflow() -> (i32, String) {
+    빠파마밦다빠빠따다자맣밝받파마밦타망희
+}
+
+#[아희(컴파일=네이티브)]
+fn underflow_native() -> (i32, String) {
+    빠파마밦다빠빠따다자맣밝받파마밦타망희
+}
+
+#[아희]
+fn underflow_swap() -> (i32, String) {
+    반파빠망망희
+}
+
+#[아희(컴파일=네이티브)]
+fn underflow_swap_native() -> (i32, String) {
+    반파빠망망희
+}
+
+#[아희]
+fn underflow_queue() -> (i32, String) {
+    상받타망희
+}
+
+#[아희(컴파일=네이티브)]
+fn underflow_queue_native() -> (i32, String) {
+    상받타망희
+}
+
+#[아희(컴파일=네이티브)]
+fn division_by_zero_native() -> i32 {
+    받반반타나희
+}
+
+#[test]
+fn test_native() {
+    assert_eq!(42, the_answer_native());
+    assert_eq!("Hello, world!\n", &hello_world_native());
+    assert_eq!((144, "23581321345589144233".to_string()), fibo_native());
+    assert_eq!("46663", &codepoint_native("뙇"));
+    assert_eq!("안녕하세요?\n", &alt_quote_native());
+    assert_eq!(
+        (String::new(), 3433683820292512484657849089281),
+        nine_to_the_thirty_second_native()
+    );
+    assert_eq!("4-2", &queue_native());
+    assert_eq!(count_down(), count_down_native());
+    assert_eq!(underflow(), underflow_native());
+    assert_eq!((2, "22".to_string()), underflow_swap_native());
+    assert_eq!(underflow_swap(), underflow_swap_native());
+    assert_eq!((0, "0".to_string()), underflow_queue_native());
+    assert_eq!(underflow_queue(), underflow_queue_native());
+}
+
+#[test]
+#[should_panic(expected = "division by zero")]
+fn test_native_division_by_zero() {
+    division_by_zero_native();
+}
+
+/// 실행할 때마다 커버리지를 `SEVEN_IF_NONZERO_COVERAGE`에 모읍니다.
+#[아희(입력=인자(input), 커버리지=SEVEN_IF_NONZERO_COVERAGE)]
+fn seven_if_nonzero(input: &str) -> i32 {
+    방추;
+    ㅇ붉;
+    ㅇ희;
+    ㅇ희
+}
+
+#[test]
+fn test_coverage() {
+    use aheui_core::{Address, BranchCount};
+
+    assert_eq!(7, seven_if_nonzero("5"));
+    let report = SEVEN_IF_NONZERO_COVERAGE.report();
+    assert_eq!(report.cells, 5);
+    assert_eq!(report.uncovered, vec![Address { row: 3, col: 1 }]);
+    assert_eq!(
+        report.one_sided,
+        vec![(
+            Address { row: 0, col: 1 },
+            BranchCount {
+                forward: 1,
+                reversed: 0
+            }
+        )]
+    );
+
+    assert_eq!(0, seven_if_nonzero("0"));
+    let report = SEVEN_IF_NONZERO_COVERAGE.report();
+    assert_eq!(report.covered, 5);
+    assert!(report.uncovered.is_empty());
+    assert!(report.one_sided.is_empty());
+}